@@ -0,0 +1,254 @@
+//!Setting up arbitrary positions piece by piece, for board editors and
+//!study tools. Nothing is checked until `build`, so the board can go
+//!through impossible states while it is being edited.
+use core::fmt;
+
+use crate::position::Position;
+use crate::repr::*;
+use crate::ChessError;
+
+///Why `PositionBuilder::build` rejected a position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PositionError {
+    ///A side has no king or more than one, with how many it has.
+    KingCount(Color, u32),
+    ///More than 16 pieces or more than 8 pawns.
+    TooManyPieces(Color),
+    PawnOnBackRank(Square),
+    ///The side that just moved is still in check.
+    OpponentInCheck,
+    ///The castling right is set but the king or rook isn't on its starting
+    ///square. The move is `KingsideCastle` or `QueensideCastle`.
+    InvalidCastling(Color, Move),
+    ///Not a square a pawn could have just skipped over.
+    InvalidEnPassant(Square),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::KingCount(color, count) => write!(f, "{:?} has {} kings instead of 1", color, count),
+            PositionError::TooManyPieces(color) => write!(f, "{:?} has too many pieces", color),
+            PositionError::PawnOnBackRank(square) => write!(f, "there is a pawn on {}", square),
+            PositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            PositionError::InvalidCastling(color, side) => write!(f, "{:?} can't have the right to {}", color, side),
+            PositionError::InvalidEnPassant(square) => write!(f, "{} is not a valid en passant square", square),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PositionError {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PositionBuilder {
+    board: Colored<Pieces>,
+    turn: Color,
+    castling_rights: Colored<CastlingRights>,
+    en_passant: Option<Square>,
+    half_moves: u16,
+    full_moves: u16,
+}
+
+impl Default for PositionBuilder {
+    fn default() -> PositionBuilder {
+        PositionBuilder::new()
+    }
+}
+
+impl From<Position> for PositionBuilder {
+    fn from(position: Position) -> PositionBuilder {
+        PositionBuilder {
+            board: *position.board(),
+            turn: position.turn,
+            castling_rights: position.castling_rights,
+            en_passant: position.en_passant,
+            half_moves: position.half_moves,
+            full_moves: position.full_moves,
+        }
+    }
+}
+
+impl PositionBuilder {
+    ///An empty board, white to move, no castling rights.
+    pub fn new() -> PositionBuilder {
+        let no_castling = CastlingRights::new(false, false);
+        PositionBuilder {
+            board: Colored(Pieces::empty(), Pieces::empty()),
+            turn: Color::White,
+            castling_rights: Colored(no_castling, no_castling),
+            en_passant: None,
+            half_moves: 0,
+            full_moves: 1,
+        }
+    }
+
+    pub fn at(&self, square: Square) -> Option<(Color, Piece)> {
+        self.board.at(square)
+    }
+
+    ///Puts a piece on `square`, replacing whatever was there.
+    pub fn put(&mut self, square: Square, color: Color, piece: Piece) -> &mut PositionBuilder {
+        self.remove(square);
+        self.board[color][piece].set(square);
+        self
+    }
+
+    pub fn remove(&mut self, square: Square) -> &mut PositionBuilder {
+        self.board[Color::White].unset(square);
+        self.board[Color::Black].unset(square);
+        self
+    }
+
+    ///Removes every piece. The other settings are kept.
+    pub fn clear(&mut self) -> &mut PositionBuilder {
+        self.board = Colored(Pieces::empty(), Pieces::empty());
+        self
+    }
+
+    pub fn turn(&mut self, turn: Color) -> &mut PositionBuilder {
+        self.turn = turn;
+        self
+    }
+
+    pub fn castling_rights(&mut self, color: Color, rights: CastlingRights) -> &mut PositionBuilder {
+        self.castling_rights[color] = rights;
+        self
+    }
+
+    pub fn en_passant(&mut self, square: Option<Square>) -> &mut PositionBuilder {
+        self.en_passant = square;
+        self
+    }
+
+    pub fn half_moves(&mut self, half_moves: u16) -> &mut PositionBuilder {
+        self.half_moves = half_moves;
+        self
+    }
+
+    pub fn full_moves(&mut self, full_moves: u16) -> &mut PositionBuilder {
+        self.full_moves = full_moves;
+        self
+    }
+
+    ///Checks that the position could occur in a game and returns it.
+    pub fn build(&self) -> Result<Position, ChessError> {
+        self.validate().map_err(ChessError::InvalidPosition)?;
+
+        let mut position = Position::new(self.board, self.turn, self.castling_rights,
+                                         self.en_passant, self.half_moves);
+        position.full_moves = self.full_moves;
+        Ok(position)
+    }
+
+    fn validate(&self) -> Result<(), PositionError> {
+        let board = &self.board;
+
+        for &color in [Color::White, Color::Black].iter() {
+            let kings = board[color][Piece::King].count();
+            if kings != 1 {
+                return Err(PositionError::KingCount(color, kings));
+            }
+            if board[color].occupied().count() > 16 || board[color][Piece::Pawn].count() > 8 {
+                return Err(PositionError::TooManyPieces(color));
+            }
+        }
+
+        let pawns = board[Color::White][Piece::Pawn] | board[Color::Black][Piece::Pawn];
+        let back_ranks = Rank::First.to_bitboard() | Rank::Eighth.to_bitboard();
+        if let Some(square) = (pawns & back_ranks).lsb() {
+            return Err(PositionError::PawnOnBackRank(square));
+        }
+
+        if board.in_check(self.turn.other()) {
+            return Err(PositionError::OpponentInCheck);
+        }
+
+        for &color in [Color::White, Color::Black].iter() {
+            let rights = self.castling_rights[color];
+            for &(allowed, side) in [(rights.kingside, Move::KingsideCastle),
+                                     (rights.queenside, Move::QueensideCastle)].iter() {
+                let (king, _, rook, _) = side.castling_squares(color);
+                if allowed && !(board[color][Piece::King].at(king) && board[color][Piece::Rook].at(rook)) {
+                    return Err(PositionError::InvalidCastling(color, side));
+                }
+            }
+        }
+
+        if let Some(square) = self.en_passant {
+            // The opponent just pushed a pawn two squares over `square`.
+            let opponent = self.turn.other();
+            let forward = Direction::pawn(opponent);
+            let plausible = Rank::from(square) == Rank::Third.relative(opponent) &&
+                            board.at(square).is_none() &&
+                            board.at(square + forward.opposite()).is_none() &&
+                            board.at(square + forward) == Some((opponent, Piece::Pawn));
+            if !plausible {
+                return Err(PositionError::InvalidEnPassant(square));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_from_scratch() {
+        let position = PositionBuilder::new()
+            .put(Square::E1, Color::White, Piece::King)
+            .put(Square::H1, Color::White, Piece::Rook)
+            .put(Square::E8, Color::Black, Piece::King)
+            .put(Square::D4, Color::Black, Piece::Pawn)
+            .put(Square::E4, Color::White, Piece::Pawn)
+            .turn(Color::Black)
+            .castling_rights(Color::White, CastlingRights::new(true, false))
+            .en_passant(Some(Square::E3))
+            .full_moves(12)
+            .build()
+            .unwrap();
+        assert_eq!(position.to_fen(), "4k3/8/8/8/3pP3/8/8/4K2R b K e3 0 12");
+        assert_eq!(Position::from_fen(&position.to_fen()).unwrap(), position);
+        assert!(position.legal_moves().contains(&Move::EnPassant(Square::D4, Square::E3)));
+    }
+
+    #[test]
+    fn editing_keeps_one_piece_per_square() {
+        let mut builder = PositionBuilder::from(Position::starting());
+        builder.put(Square::E2, Color::Black, Piece::Queen).remove(Square::D7);
+        assert_eq!(builder.at(Square::E2), Some((Color::Black, Piece::Queen)));
+        assert_eq!(builder.at(Square::D7), None);
+        assert!(builder.clear().build().is_err());
+    }
+
+    #[test]
+    fn detailed_errors() {
+        let error = |builder: &PositionBuilder| match builder.build() {
+            Err(ChessError::InvalidPosition(error)) => error,
+            other => panic!("expected an invalid position, got {:?}", other),
+        };
+
+        let mut builder = PositionBuilder::new();
+        builder.put(Square::E1, Color::White, Piece::King);
+        assert_eq!(error(&builder), PositionError::KingCount(Color::Black, 0));
+
+        builder.put(Square::E8, Color::Black, Piece::King).put(Square::A8, Color::White, Piece::Pawn);
+        assert_eq!(error(&builder), PositionError::PawnOnBackRank(Square::A8));
+
+        builder.put(Square::A8, Color::White, Piece::Rook);
+        assert_eq!(error(&builder), PositionError::OpponentInCheck);
+
+        builder.remove(Square::A8).castling_rights(Color::White, CastlingRights::new(false, true));
+        assert_eq!(error(&builder), PositionError::InvalidCastling(Color::White, Move::QueensideCastle));
+
+        builder.castling_rights(Color::White, CastlingRights::new(false, false)).en_passant(Some(Square::D6));
+        assert_eq!(error(&builder), PositionError::InvalidEnPassant(Square::D6));
+
+        builder.en_passant(None);
+        assert!(builder.build().is_ok());
+    }
+}
@@ -1,24 +1,44 @@
-use lazy_static::lazy_static;
+//!Lookup tables used by the move generator. Everything here is computed
+//!at compile time by `const fn`s, so there is no initialization cost and no
+//!allocation at runtime.
 use crate::repr::*;
-use strum::IntoEnumIterator;
-use std::collections::HashMap;
-
-lazy_static! {
-    pub static ref SLIDE_HORIZONTAL: HashMap<(Square, Bitboard), (Bitboard, Bitboard)> = generate_slide_horizontal();
-    pub static ref SLIDE_VERTICAL: HashMap<(Square, Bitboard), (Bitboard, Bitboard)> = generate_slide_vertical();
-    pub static ref SLIDE_MAIN_DIAGONAL: HashMap<(Square, Bitboard), (Bitboard, Bitboard)> = generate_slide_main_diagonal();
-    pub static ref SLIDE_SECOND_DIAGONAL: HashMap<(Square, Bitboard), (Bitboard, Bitboard)> = generate_slide_second_diagonal();
-    pub static ref KNIGHT_ATTACKS: [Bitboard; 64] = knight_moves();
-    pub static ref KING_ATTACKS: [Bitboard; 64] = king_moves();
-    pub static ref PAWN_ATTACKS: Colored<[Bitboard; 64]> = pawn_attacks();
-
-    pub static ref FILES_BETWEEN: [[Bitboard; 8]; 8] = generate_files();
-    pub static ref RANKS_BETWEEN: [[Bitboard; 8]; 8] = generate_ranks();
-    pub static ref MAIN_DIAGS_BETWEEN: [[Bitboard; 15]; 15] = generate_main_diags();
-    pub static ref SECOND_DIAGS_BETWEEN: [[Bitboard; 15]; 15] = generate_second_diags();
+
+///For every square and every occupancy of the rank through it: the squares
+///attacked along the rank, and the squares behind the first blocker up to
+///and including the second one, which is where a pinned piece would be.
+pub static SLIDE_HORIZONTAL: [[(Bitboard, Bitboard); 256]; 64] = generate_slides([-1, 1], LineIndex::File);
+pub static SLIDE_VERTICAL: [[(Bitboard, Bitboard); 256]; 64] = generate_slides([8, -8], LineIndex::Rank);
+pub static SLIDE_MAIN_DIAGONAL: [[(Bitboard, Bitboard); 256]; 64] = generate_slides([7, -7], LineIndex::File);
+pub static SLIDE_SECOND_DIAGONAL: [[(Bitboard, Bitboard); 256]; 64] = generate_slides([9, -9], LineIndex::File);
+
+pub static KNIGHT_ATTACKS: [Bitboard; 64] = generate_leaps([6, 15, 17, 10, -6, -15, -17, -10]);
+pub static KING_ATTACKS: [Bitboard; 64] = generate_leaps([7, 8, 9, 1, -7, -8, -9, -1]);
+pub static PAWN_ATTACKS: Colored<[Bitboard; 64]> = Colored(generate_leaps([7, 9, 7, 9, 7, 9, 7, 9]),
+                                                           generate_leaps([-7, -9, -7, -9, -7, -9, -7, -9]));
+
+///`RAYS[d][sq]` is every square reached from `sq` going in `Direction::ALL[d]`,
+///up to the edge of the board.
+pub static RAYS: [[Bitboard; 64]; 8] = generate_rays();
+///The squares strictly between two squares on a common line, empty if they
+///don't share one.
+pub static BETWEEN: [[Bitboard; 64]; 64] = generate_between();
+///The whole line through two squares, edge to edge and including both, empty
+///if they don't share one.
+pub static LINE: [[Bitboard; 64]; 64] = generate_lines();
+
+pub static ZOBRIST: Zobrist = generate_zobrist();
+
+///Random keys for Zobrist hashing, one for every (color, piece, square),
+///castling right, en passant file and for black to move.
+pub struct Zobrist {
+    pub pieces: Colored<[[u64; 64]; 6]>,
+    ///Kingside then queenside.
+    pub castling: Colored<[u64; 2]>,
+    pub en_passant: [u64; 8],
+    pub black_to_move: u64,
 }
 
-pub const STARTING_POS_FEN: &'static str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+pub const STARTING_POS_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
 pub const SAFE_KING_CASTLE: Colored<Bitboard> = Colored(Bitboard(0x00_00_00_00_00_00_00_70),
                                                         Bitboard(0x70_00_00_00_00_00_00_00));
@@ -88,336 +108,293 @@ pub const SECOND_DIAG: [Bitboard; 15] = [
 ];
 
 
-fn generate_files() ->  [[Bitboard; 8]; 8] {
-    let mut files = [[Bitboard(0); 8]; 8]; 
-    for i in 0..8 {
-        for j in 0..8 {
-            for k in std::cmp::min(i,j)..std::cmp::max(i,j)+1 {
-                files[i][j] = files[i][j] | FILE[k];
-            }
-        }
-    }
 
-    files
+///Which coordinate of the squares on a line numbers the bits of its
+///occupancy index. Files are unique along ranks and diagonals, ranks are
+///unique along files.
+#[derive(Copy, Clone)]
+enum LineIndex {
+    File,
+    Rank,
 }
 
-fn generate_ranks() ->  [[Bitboard; 8]; 8] {
-    let mut ranks = [[Bitboard(0); 8]; 8]; 
-    for i in 0..8 {
-        for j in 0..8 {
-            for k in std::cmp::min(i,j)..std::cmp::max(i,j)+1 {
-                ranks[i][j] = ranks[i][j] | RANK[k];
-            }
-        }
-    }
+///The occupancy of the rank through `square`, one bit per file.
+pub fn rank_occupancy(square: Square, occupied: Bitboard) -> usize {
+    ((occupied.0 >> (8 * square.rank())) & 0xFF) as usize
+}
 
-    ranks
+///The occupancy of the file through `square`, one bit per rank. The
+///multiplication moves the bit of every rank to a distinct bit of the top
+///byte.
+pub fn file_occupancy(square: Square, occupied: Bitboard) -> usize {
+    let file = (occupied.0 >> square.file()) & FILE[0].0;
+    (file.wrapping_mul(0x0102040810204080) >> 56) as usize
 }
 
-fn generate_main_diags() ->  [[Bitboard; 15]; 15] {
-    let mut main_diags = [[Bitboard(0); 15]; 15]; 
-    for i in 0..15 {
-        for j in 0..15 {
-            for k in std::cmp::min(i,j)..std::cmp::max(i,j)+1 {
-                main_diags[i][j] = main_diags[i][j] | MAIN_DIAG[k];
-            }
-        }
-    }
+///The occupancy of a diagonal, one bit per file. Every square of a
+///diagonal is on a different file, so multiplying by the a-file collapses
+///them into the top byte without carries.
+fn diagonal_occupancy(diagonal: Bitboard, occupied: Bitboard) -> usize {
+    ((occupied.0 & diagonal.0).wrapping_mul(FILE[0].0) >> 56) as usize
+}
 
-    main_diags
+pub fn horizontal_slide(square: Square, occupied: Bitboard) -> (Bitboard, Bitboard) {
+    SLIDE_HORIZONTAL[square as usize][rank_occupancy(square, occupied)]
 }
 
-fn generate_second_diags() ->  [[Bitboard; 15]; 15] {
-    let mut second_diags = [[Bitboard(0); 15]; 15]; 
-    for i in 0..15 {
-        for j in 0..15 {
-            for k in std::cmp::min(i,j)..std::cmp::max(i,j)+1 {
-                second_diags[i][j] = second_diags[i][j] | SECOND_DIAG[k];
-            }
-        }
-    }
+pub fn vertical_slide(square: Square, occupied: Bitboard) -> (Bitboard, Bitboard) {
+    SLIDE_VERTICAL[square as usize][file_occupancy(square, occupied)]
+}
+
+pub fn main_diagonal_slide(square: Square, occupied: Bitboard) -> (Bitboard, Bitboard) {
+    let diagonal = MAIN_DIAG[square.main_diag() as usize];
+    SLIDE_MAIN_DIAGONAL[square as usize][diagonal_occupancy(diagonal, occupied)]
+}
 
-    second_diags
+pub fn second_diagonal_slide(square: Square, occupied: Bitboard) -> (Bitboard, Bitboard) {
+    let diagonal = SECOND_DIAG[square.second_diag() as usize];
+    SLIDE_SECOND_DIAGONAL[square as usize][diagonal_occupancy(diagonal, occupied)]
 }
 
+pub fn between(lhs: Square, rhs: Square) -> Bitboard {
+    BETWEEN[lhs as usize][rhs as usize]
+}
 
+pub fn line(lhs: Square, rhs: Square) -> Bitboard {
+    LINE[lhs as usize][rhs as usize]
+}
 
+pub fn ray(square: Square, direction: Direction) -> Bitboard {
+    RAYS[direction.index()][square as usize]
+}
 
+///Index offsets in the order of `Direction::ALL`.
+const DIRECTION_OFFSETS: [i8; 8] = [8, 9, 1, -7, -8, -9, -1, 7];
 
+///The next square in a direction given as an index offset, or `None` when
+///that would leave the board or wrap around to the other side.
+const fn step(square: i8, offset: i8, max_file_change: i8) -> Option<i8> {
+    let next = square + offset;
+    if next < 0 || next > 63 || (next % 8 - square % 8).abs() > max_file_change {
+        return None;
+    }
+    Some(next)
+}
 
-fn generate_slide_horizontal() -> HashMap<(Square, Bitboard), (Bitboard, Bitboard)>{
-    let mut memo = HashMap::new();
+const fn generate_slides(directions: [i8; 2], index: LineIndex) -> [[(Bitboard, Bitboard); 256]; 64] {
+    let mut table = [[(Bitboard(0), Bitboard(0)); 256]; 64];
 
-    for sq in Square::iter() {    
-        for pos in 0..=255u8 {
-            let mut key = 0u64;
+    let mut square = 0;
+    while square < 64 {
+        let mut occupancy = 0;
+        while occupancy < 256 {
             let mut attack = 0u64;
             let mut pinned = 0u64;
 
-            let mut attack_flag = true;
-            let mut pinned_flag = false;
-
-            for left in sq.ray(Direction::Left) {
-                let rank = left.file();
-                
-                if attack_flag {
-                    attack |= left.to_bitboard().0;
-                }
-                if pinned_flag {
-                    pinned |= left.to_bitboard().0;
-                }
-                if (1<<rank)&pos != 0{
-                    key |= left.to_bitboard().0;
-                    pinned_flag = if attack_flag { true } else { false };
-                    attack_flag = false;
-                }
-            }
-            attack_flag = true;
-            pinned_flag = false;
-            for right in sq.ray(Direction::Right) {        
-                let rank = right.file();
-                
-                if attack_flag {
-                    attack |= right.to_bitboard().0;
-                }
-                if pinned_flag {
-                    pinned |= right.to_bitboard().0;
-                }
-                if (1<<rank)&pos != 0 {
-                    key |= right.to_bitboard().0;
-                    pinned_flag = if attack_flag { true } else { false };
-                    attack_flag = false
+            let mut d = 0;
+            while d < directions.len() {
+                let mut blockers = 0;
+                let mut current = square as i8;
+                while let Some(next) = step(current, directions[d], 1) {
+                    if blockers > 1 {
+                        break;
+                    }
+                    current = next;
+                    match blockers {
+                        0 => attack |= 1 << current,
+                        _ => pinned |= 1 << current,
+                    }
+                    let bit = match index {
+                        LineIndex::File => current % 8,
+                        LineIndex::Rank => current / 8,
+                    };
+                    if occupancy & (1 << bit) != 0 {
+                        blockers += 1;
+                    }
                 }
+                d += 1;
             }
-            let attack = Bitboard(attack);
-            let pinned = Bitboard(pinned);
-            memo.insert((sq,Bitboard(key)), (attack, pinned));
-            key |= sq.to_bitboard().0;
-            memo.insert((sq,Bitboard(key)), (attack, pinned));
+
+            table[square][occupancy] = (Bitboard(attack), Bitboard(pinned));
+            occupancy += 1;
         }
+        square += 1;
     }
-    memo
+    table
 }
 
+const fn generate_leaps(offsets: [i8; 8]) -> [Bitboard; 64] {
+    let mut attacks = [Bitboard(0); 64];
 
-fn generate_slide_vertical() -> HashMap<(Square,Bitboard), (Bitboard, Bitboard)>  {
-    let mut memo = HashMap::new();
-
-    for sq in Square::iter() {    
-        for pos in 0..=255u8 {
-            let mut key = 0u64;
-            let mut attack = 0u64;
-            let mut pinned = 0u64;
-
-            let mut attack_flag = true;
-            let mut pinned_flag = false;
-
-            for above in sq.ray(Direction::Up) {
-                let rank = above.rank();
-                
-                if attack_flag {
-                    attack |= above.to_bitboard().0;
-                }
-                if pinned_flag {
-                    pinned |= above.to_bitboard().0;
-                }
-                if (1<<rank)&pos != 0{
-                    key |= above.to_bitboard().0;
-                    pinned_flag = if attack_flag { true } else { false };
-                    attack_flag = false;
-                }
-            }
-            attack_flag = true;
-            pinned_flag = false;
-            for below in sq.ray(Direction::Down) {        
-                let rank = below.rank();
-                
-                if attack_flag {
-                    attack |= below.to_bitboard().0;
-                }
-                if pinned_flag {
-                    pinned |= below.to_bitboard().0;
-                }
-                if (1<<rank)&pos != 0 {
-                    key |= below.to_bitboard().0;
-                    pinned_flag = if attack_flag { true } else { false };
-                    attack_flag = false
-                }
+    let mut square = 0;
+    while square < 64 {
+        let mut i = 0;
+        while i < offsets.len() {
+            if let Some(target) = step(square as i8, offsets[i], 2) {
+                attacks[square].0 |= 1 << target;
             }
-            let attack = Bitboard(attack);
-            let pinned = Bitboard(pinned);
-            memo.insert((sq,Bitboard(key)), (attack, pinned));
-            key |= sq.to_bitboard().0;
-            memo.insert((sq,Bitboard(key)), (attack, pinned));
+            i += 1;
         }
+        square += 1;
     }
-    memo
+    attacks
 }
 
-fn generate_slide_main_diagonal() -> HashMap<(Square,Bitboard), (Bitboard, Bitboard)> {
-    let mut memo = HashMap::new();
-
-    for sq in Square::iter() {    
-        for pos in 0..=255u8 {
-            let mut key = 0u64;
-            let mut attack = 0u64;
-            let mut pinned = 0u64;
-
-            let mut attack_flag = true;
-            let mut pinned_flag = false;
-
-            for above in sq.ray(Direction::UpLeft) {
-                let rank = above.rank();
-                
-                if attack_flag {
-                    attack |= above.to_bitboard().0;
-                }
-                if pinned_flag {
-                    pinned |= above.to_bitboard().0;
-                }
-                if (1<<rank)&pos != 0{
-                    key |= above.to_bitboard().0;
-                    pinned_flag = if attack_flag { true } else { false };
-                    attack_flag = false;
-                }
-            }
-            attack_flag = true;
-            pinned_flag = false;
-            for below in sq.ray(Direction::DownRight) {        
-                let rank = below.rank();
-                
-                if attack_flag {
-                    attack |= below.to_bitboard().0;
-                }
-                if pinned_flag {
-                    pinned |= below.to_bitboard().0;
-                }
-                if (1<<rank)&pos != 0 {
-                    key |= below.to_bitboard().0;
-                    pinned_flag = if attack_flag { true } else { false };
-                    attack_flag = false
-                }
+const fn generate_rays() -> [[Bitboard; 64]; 8] {
+    let mut rays = [[Bitboard(0); 64]; 8];
+
+    let mut d = 0;
+    while d < 8 {
+        let mut square = 0;
+        while square < 64 {
+            let mut current = square as i8;
+            while let Some(next) = step(current, DIRECTION_OFFSETS[d], 1) {
+                current = next;
+                rays[d][square].0 |= 1 << current;
             }
-            let attack = Bitboard(attack);
-            let pinned = Bitboard(pinned);
-            memo.insert((sq,Bitboard(key)), (attack, pinned));
-            key |= sq.to_bitboard().0;
-            memo.insert((sq,Bitboard(key)), (attack, pinned));
+            square += 1;
         }
+        d += 1;
     }
-    memo
-
+    rays
 }
 
-
-fn generate_slide_second_diagonal() -> HashMap<(Square,Bitboard), (Bitboard, Bitboard)> {
-    let mut memo = HashMap::new();
-
-    for sq in Square::iter() {    
-        for pos in 0..=255u8 {
-            let mut key = 0u64;
-            let mut attack = 0u64;
-            let mut pinned = 0u64;
-
-            let mut attack_flag = true;
-            let mut pinned_flag = false;
-
-            for above in sq.ray(Direction::UpRight) {
-                let rank = above.rank();
-                
-                if attack_flag {
-                    attack |= above.to_bitboard().0;
-                }
-                if pinned_flag {
-                    pinned |= above.to_bitboard().0;
-                }
-                if (1<<rank)&pos != 0{
-                    key |= above.to_bitboard().0;
-                    pinned_flag = if attack_flag { true } else { false };
-                    attack_flag = false;
-                }
+const fn generate_between() -> [[Bitboard; 64]; 64] {
+    let mut table = [[Bitboard(0); 64]; 64];
+
+    let mut square = 0;
+    while square < 64 {
+        let mut d = 0;
+        while d < 8 {
+            let mut passed = 0u64;
+            let mut current = square as i8;
+            while let Some(next) = step(current, DIRECTION_OFFSETS[d], 1) {
+                current = next;
+                table[square][current as usize] = Bitboard(passed);
+                passed |= 1 << current;
             }
-            attack_flag = true;
-            pinned_flag = false;
-            for below in sq.ray(Direction::DownLeft) {        
-                let rank = below.rank();
-                
-                if attack_flag {
-                    attack |= below.to_bitboard().0;
-                }
-                if pinned_flag {
-                    pinned |= below.to_bitboard().0;
-                }
-                if (1<<rank)&pos != 0 {
-                    key |= below.to_bitboard().0;
-                    pinned_flag = if attack_flag { true } else { false };
-                    attack_flag = false
-                }
-            }
-            let attack = Bitboard(attack);
-            let pinned = Bitboard(pinned);
-            memo.insert((sq,Bitboard(key)), (attack, pinned));
-            key |= sq.to_bitboard().0;
-            memo.insert((sq,Bitboard(key)), (attack, pinned));
+            d += 1;
         }
+        square += 1;
     }
-    memo
-
+    table
 }
 
-
-fn knight_moves() -> [Bitboard; 64] {
-    let mut attacks = [Bitboard(0); 64]; 
-    
-    for sq in 0..64i8 {
-        for dp in [6,15,17,10,-6,-15,-17,-10i8].iter() {
-            if sq + dp < 0 || sq + dp > 63 {
-                continue;
+const fn generate_lines() -> [[Bitboard; 64]; 64] {
+    let rays = generate_rays();
+    let mut table = [[Bitboard(0); 64]; 64];
+
+    let mut square = 0;
+    while square < 64 {
+        let mut d = 0;
+        while d < 8 {
+            let line = rays[d][square].0 | rays[(d + 4) % 8][square].0 | 1 << square;
+            let mut current = square as i8;
+            while let Some(next) = step(current, DIRECTION_OFFSETS[d], 1) {
+                current = next;
+                table[square][current as usize] = Bitboard(line);
             }
-            if ((sq+dp)%8 - sq%8).abs() > 2 {
-                continue;
-            }
-            let attack = Square::from((sq+dp) as u8);
-            attacks[sq as usize].0 |= attack.to_bitboard().0;
+            d += 1;
         }
+        square += 1;
     }
-    attacks
+    table
 }
 
-fn king_moves() -> [Bitboard; 64] {
-    let mut attacks = [Bitboard(0); 64]; 
-    
-    for sq in 0..64i8 {
-        for dp in [7,8,9,1,-7,-8,-9,-1].iter() {
-            if sq + dp < 0 || sq + dp > 63 {
-                continue;
-            }
-            if ((sq+dp)%8 - sq%8).abs() > 2 {
-                continue;
-            }
-            let attack = Square::from((sq+dp) as u8);
-            attacks[sq as usize].0 |= attack.to_bitboard().0;
+///splitmix64, the same generator as [`crate::random::Rng`].
+const fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+const fn generate_zobrist() -> Zobrist {
+    // Any fixed seed works, it only has to be the same on every run.
+    let mut state = 0x5A0B_2157;
+
+    let mut pieces = Colored([[0u64; 64]; 6], [[0u64; 64]; 6]);
+    let mut piece = 0;
+    while piece < 6 {
+        let mut square = 0;
+        while square < 64 {
+            pieces.0[piece][square] = next_random(&mut state);
+            pieces.1[piece][square] = next_random(&mut state);
+            square += 1;
         }
+        piece += 1;
+    }
+
+    let mut en_passant = [0u64; 8];
+    let mut file = 0;
+    while file < 8 {
+        en_passant[file] = next_random(&mut state);
+        file += 1;
+    }
+
+    Zobrist {
+        pieces,
+        castling: Colored([next_random(&mut state), next_random(&mut state)],
+                          [next_random(&mut state), next_random(&mut state)]),
+        en_passant,
+        black_to_move: next_random(&mut state),
     }
-    attacks
 }
 
-fn pawn_attacks() -> Colored<[Bitboard; 64]> {
-    let mut attacks = Colored([Bitboard(0);64], [Bitboard(0);64]);
-    
-    for sq in 0..64i8 {
-        for dp in [7,9, -9, -7].iter() {
-            if sq + dp < 0 || sq + dp > 63 {
-                continue;
-            }
-            if ((sq+dp)%8 - sq%8).abs() > 2 {
-                continue;
-            }
-            let attack = Square::from((sq+dp) as u8);
-            let color = if *dp > 0 { Color::White } else { Color::Black };
-            attacks[color][sq as usize].0 |= attack.to_bitboard().0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaper_tables() {
+        assert_eq!(KNIGHT_ATTACKS[Square::A1 as usize], Bitboard(0x0000000000020400));
+        assert_eq!(KNIGHT_ATTACKS[Square::D4 as usize].count(), 8);
+        assert_eq!(KING_ATTACKS[Square::H8 as usize], Bitboard(0x40C0000000000000));
+        assert_eq!(PAWN_ATTACKS[Color::White][Square::A2 as usize], Square::B3.to_bitboard());
+        assert_eq!(PAWN_ATTACKS[Color::Black][Square::E7 as usize], Square::D6.to_bitboard() | Square::F6.to_bitboard());
+    }
+
+    #[test]
+    fn slider_tables() {
+        // Rook on d4 with blockers on b4, f4 and d7, pinned pieces would be
+        // behind b4 and f4 on the rank and behind d7 on the file.
+        let mut occupied = Bitboard::empty();
+        for &square in [Square::B4, Square::F4, Square::H4, Square::D7, Square::D4].iter() {
+            occupied.set(square);
         }
+        let (attack, pinned) = horizontal_slide(Square::D4, occupied);
+        assert_eq!(attack, Bitboard(0x0000000036000000));
+        assert_eq!(pinned, Square::A4.to_bitboard() | Square::G4.to_bitboard() | Square::H4.to_bitboard());
+
+        let (attack, pinned) = vertical_slide(Square::D4, occupied);
+        assert_eq!(attack, Bitboard(0x0008080800080808));
+        assert_eq!(pinned, Square::D8.to_bitboard());
+
+        let (attack, _) = main_diagonal_slide(Square::D4, occupied);
+        let expected = [Square::A7, Square::B6, Square::C5, Square::E3, Square::F2, Square::G1];
+        assert_eq!(attack, expected.iter().fold(Bitboard::empty(), |bb, sq| bb | sq.to_bitboard()));
+        let (attack, _) = second_diagonal_slide(Square::D4, Bitboard::empty());
+        assert_eq!(attack, Bitboard(0x8040201000040201));
+    }
+
+    #[test]
+    fn geometry_tables() {
+        let squares = |squares: &[Square]| squares.iter().copied().collect::<Bitboard>();
+
+        assert_eq!(between(Square::A1, Square::D4), squares(&[Square::B2, Square::C3]));
+        assert_eq!(between(Square::D4, Square::A1), between(Square::A1, Square::D4));
+        assert_eq!(between(Square::E1, Square::E2), Bitboard::empty());
+        assert_eq!(between(Square::A1, Square::B3), Bitboard::empty());
+        assert_eq!(between(Square::H1, Square::A1), Bitboard(0x7E));
+
+        assert_eq!(line(Square::C3, Square::E5), Bitboard(0x8040201008040201));
+        assert_eq!(line(Square::E2, Square::E7), File::E.to_bitboard());
+        assert_eq!(line(Square::A1, Square::B3), Bitboard::empty());
+
+        assert_eq!(ray(Square::D4, Direction::UpLeft), squares(&[Square::C5, Square::B6, Square::A7]));
+        assert_eq!(ray(Square::H4, Direction::Right), Bitboard::empty());
+        assert_eq!(ray(Square::A2, Direction::Down), Square::A1.to_bitboard());
     }
-    
-    attacks
 }
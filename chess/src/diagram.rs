@@ -0,0 +1,164 @@
+//!Reading positions from text diagrams, like the output of
+//!`Position::to_ascii` or boards pasted from books and forums:
+//!
+//!```text
+//!r n b q k b n r 8
+//!p p p p p p p p 7
+//!. . . . . . . . 6
+//!```
+//!
+//!Empty squares can be `.`, `-` or `_`, spaces, `|` borders and rank numbers
+//!are ignored, and lines with `+` or any other text are skipped. Uppercase
+//!letters are white pieces and lowercase ones black, unless the piece is
+//!colored with ANSI escapes the way `to_ascii` does it: cyan for white and
+//!magenta for black.
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::builder::PositionBuilder;
+use crate::repr::*;
+use crate::ChessError;
+
+impl PositionBuilder {
+    ///Reads a diagram with white at the bottom. Castling rights are given
+    ///wherever king and rook are still on their starting squares, and black
+    ///is to move only if black is in check. Use the setters on the result to
+    ///override either.
+    pub fn from_diagram(diagram: &str) -> Result<PositionBuilder, ChessError> {
+        let ranks: Vec<Vec<Option<(Color, Piece)>>> = diagram.lines().filter_map(parse_rank).collect();
+        if ranks.len() != 8 {
+            return Err(ChessError::ParseDiagram(format!("found {} ranks instead of 8", ranks.len())));
+        }
+
+        let mut builder = PositionBuilder::new();
+        for (rank, squares) in Rank::ALL.iter().rev().zip(ranks.iter()) {
+            for (&file, &square) in File::ALL.iter().zip(squares.iter()) {
+                if let Some((color, piece)) = square {
+                    builder.put(Square::new(file, *rank), color, piece);
+                }
+            }
+        }
+
+        for &color in [Color::White, Color::Black].iter() {
+            let on = |square: Square, piece: Piece| builder.at(square) == Some((color, piece));
+            let (king, _, kingside_rook, _) = Move::KingsideCastle.castling_squares(color);
+            let (_, _, queenside_rook, _) = Move::QueensideCastle.castling_squares(color);
+            let rights = CastlingRights::new(on(king, Piece::King) && on(kingside_rook, Piece::Rook),
+                                             on(king, Piece::King) && on(queenside_rook, Piece::Rook));
+            builder.castling_rights(color, rights);
+        }
+
+        let mut black_to_move = builder;
+        let black_in_check = black_to_move.turn(Color::Black).build().is_ok_and(|p| p.in_check());
+        builder.turn(if black_in_check { Color::Black } else { Color::White });
+
+        Ok(builder)
+    }
+}
+
+///The eight squares of a line that looks like a rank, `None` for anything
+///else.
+fn parse_rank(line: &str) -> Option<Vec<Option<(Color, Piece)>>> {
+    if line.contains('+') {
+        return None;
+    }
+
+    let mut squares = Vec::new();
+    let mut ansi_color = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => {
+                let mut code = "".to_owned();
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                    code.push(c);
+                }
+                ansi_color = ansi_to_color(&code, ansi_color);
+            }
+            '.' | '-' | '_' => squares.push(None),
+            c if c.is_whitespace() || c.is_ascii_digit() || c == '|' => {}
+            c => {
+                let piece = Piece::ALL.iter().copied().find(|piece| {
+                    piece.to_ascii().eq_ignore_ascii_case(&c)
+                })?;
+                let case_color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                squares.push(Some((ansi_color.unwrap_or(case_color), piece)));
+            }
+        }
+    }
+
+    match squares.len() {
+        8 => Some(squares),
+        _ => None,
+    }
+}
+
+///The color a select graphic rendition escape like `[36` switches to.
+fn ansi_to_color(code: &str, current: Option<Color>) -> Option<Color> {
+    let mut color = current;
+    for parameter in code.trim_start_matches('[').split(';') {
+        color = match parameter {
+            "36" | "96" => Some(Color::White),
+            "35" | "95" => Some(Color::Black),
+            "" | "0" | "39" => None,
+            _ => color,
+        };
+    }
+    color
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn reads_to_ascii_back() {
+        let position = Position::from_fen(KIWIPETE).unwrap();
+        let builder = PositionBuilder::from_diagram(&position.to_ascii()).unwrap();
+        assert_eq!(builder.build().unwrap(), position);
+
+        // Colors win over case, the way older versions printed pawns.
+        let colored = "\u{1b}[35mp\u{1b}[0m . . . \u{1b}[35mK\u{1b}[0m . . . 8\n";
+        let empty = ". . . . . . . . \n";
+        let diagram = [colored, empty, empty, empty, empty, empty, empty, "p . . . K . . . 1\n"].concat();
+        let builder = PositionBuilder::from_diagram(&diagram).unwrap();
+        assert_eq!(builder.at(Square::A8), Some((Color::Black, Piece::Pawn)));
+        assert_eq!(builder.at(Square::E8), Some((Color::Black, Piece::King)));
+        assert_eq!(builder.at(Square::A1), Some((Color::Black, Piece::Pawn)));
+        assert_eq!(builder.at(Square::E1), Some((Color::White, Piece::King)));
+    }
+
+    #[test]
+    fn book_diagrams() {
+        let diagram = "
+            +-----------------+
+          8 | - - - - k - - - |
+          7 | - - - - - - - - |
+          6 | - - - - - - - - |
+          5 | - - - - - - - - |
+          4 | - - - - - - - - |
+          3 | - - - - - - - - |
+          2 | - - - - Q - - - |
+          1 | R - - - K - - R |
+            +-----------------+
+              a b c d e f g h
+            Black to move.";
+        let position = PositionBuilder::from_diagram(diagram).unwrap().build().unwrap();
+        assert_eq!(position.to_fen(), "4k3/8/8/8/8/8/4Q3/R3K2R b KQ - 0 1");
+
+        let position = PositionBuilder::from_diagram(diagram).unwrap()
+            .castling_rights(Color::White, CastlingRights::new(true, false))
+            .build().unwrap();
+        assert_eq!(position.to_fen(), "4k3/8/8/8/8/8/4Q3/R3K2R b K - 0 1");
+
+        assert!(PositionBuilder::from_diagram("k . . .\nK . . .").is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//!A game as a tree of moves. Every node can have any number of children:
+//!the first one continues the main line and the others are variations.
+//!Nodes live in an arena owned by the [`Game`] and are referred to by
+//![`NodeId`], which stays valid until the node is deleted.
+use core::time::Duration;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::position::Position;
+use crate::repr::*;
+use crate::{ChessError, Chessboard};
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    ///The move that leads to this node, `None` only for the root.
+    pub mv: Option<Move>,
+    ///The position after `mv` has been played.
+    pub position: Position,
+    pub comments: Vec<String>,
+    ///Numeric annotation glyphs, e.g. 1 for `!` and 2 for `?`.
+    pub nags: Vec<u8>,
+    ///Time left on the mover's clock after the move.
+    pub clock: Option<Duration>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, position: Position, parent: Option<NodeId>) -> Node {
+        Node {
+            mv,
+            position,
+            comments: Vec::new(),
+            nags: Vec::new(),
+            clock: None,
+            parent,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    ///The main continuation first, then the variations.
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+///A game tree together with a cursor pointing at the current node.
+#[derive(Clone, Debug)]
+pub struct Game {
+    nodes: Vec<Option<Node>>,
+    cursor: NodeId,
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new(Position::starting())
+    }
+}
+
+impl Game {
+    pub fn new(position: Position) -> Game {
+        Game {
+            nodes: vec![Some(Node::new(None, position, None))],
+            cursor: NodeId(0),
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    ///Panics if the node has been deleted, use [`Game::get`] if that's
+    ///possible.
+    pub fn node(&self, id: NodeId) -> &Node {
+        self.get(id).expect("node was deleted")
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.nodes[id.0].as_mut().expect("node was deleted")
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0)?.as_ref()
+    }
+
+    ///Adds `mv` as a child of `parent` and returns the new node. If the move
+    ///is already there the existing node is returned instead.
+    pub fn add_move(&mut self, parent: NodeId, mv: Move) -> Result<NodeId, ChessError> {
+        let node = self.node(parent);
+        if let Some(&existing) = node.children.iter().find(|&&child| self.node(child).mv == Some(mv)) {
+            return Ok(existing);
+        }
+        if !node.position.legal_moves().contains(&mv) {
+            return Err(ChessError::IllegalMove(mv));
+        }
+
+        let position = node.position.make_move(mv);
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(Node::new(Some(mv), position, Some(parent))));
+        self.node_mut(parent).children.push(id);
+        Ok(id)
+    }
+
+    ///Plays `mv` from the cursor and moves the cursor to the resulting
+    ///node, creating a new variation if the move hasn't been played there.
+    pub fn play(&mut self, mv: Move) -> Result<NodeId, ChessError> {
+        self.cursor = self.add_move(self.cursor, mv)?;
+        Ok(self.cursor)
+    }
+
+    ///Depth-first from `id`, including `id` itself.
+    fn subtree(&self, id: NodeId) -> Vec<NodeId> {
+        let mut stack = vec![id];
+        let mut nodes = Vec::new();
+        while let Some(id) = stack.pop() {
+            nodes.push(id);
+            stack.extend(self.node(id).children.iter().copied());
+        }
+        nodes
+    }
+
+    fn sibling_index(&self, id: NodeId) -> Option<(NodeId, usize)> {
+        let parent = self.node(id).parent?;
+        let index = self.node(parent).children.iter().position(|&child| child == id)?;
+        Some((parent, index))
+    }
+
+    ///Removes the node and everything after it. The root can't be deleted.
+    ///If the cursor was inside the removed subtree it moves to the parent.
+    pub fn delete_variation(&mut self, id: NodeId) {
+        let (parent, index) = match self.sibling_index(id) {
+            None => return,
+            Some(sibling) => sibling,
+        };
+        self.node_mut(parent).children.remove(index);
+
+        for removed in self.subtree(id) {
+            if removed == self.cursor {
+                self.cursor = parent;
+            }
+            self.nodes[removed.0] = None;
+        }
+    }
+
+    ///Moves the variation one place up among its siblings. Returns false if
+    ///it already was the main continuation.
+    pub fn promote_variation(&mut self, id: NodeId) -> bool {
+        match self.sibling_index(id) {
+            Some((parent, index)) if index > 0 => {
+                self.node_mut(parent).children.swap(index, index - 1);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    ///Moves the variation one place down among its siblings. Returns false if
+    ///it already was the last one.
+    pub fn demote_variation(&mut self, id: NodeId) -> bool {
+        match self.sibling_index(id) {
+            Some((parent, index)) if index + 1 < self.node(parent).children.len() => {
+                self.node_mut(parent).children.swap(index, index + 1);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    ///Makes the line leading to `id` the main line of the whole game.
+    pub fn promote_to_main(&mut self, id: NodeId) {
+        let mut current = id;
+        while let Some((parent, index)) = self.sibling_index(current) {
+            let children = &mut self.node_mut(parent).children;
+            let node = children.remove(index);
+            children.insert(0, node);
+            current = parent;
+        }
+    }
+
+    ///Whether every move from the root to `id` is a main continuation.
+    pub fn is_mainline(&self, id: NodeId) -> bool {
+        let mut current = id;
+        while let Some((parent, index)) = self.sibling_index(current) {
+            if index != 0 {
+                return false;
+            }
+            current = parent;
+        }
+        true
+    }
+
+    ///The moves from the root down to `id`.
+    pub fn moves_to(&self, id: NodeId) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut current = self.node(id);
+        while let (Some(mv), Some(parent)) = (current.mv, current.parent) {
+            moves.push(mv);
+            current = self.node(parent);
+        }
+        moves.reverse();
+        moves
+    }
+
+    ///The moves of the main line of the whole game.
+    pub fn mainline(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut current = self.node(self.root());
+        while let Some(&child) = current.children.first() {
+            current = self.node(child);
+            moves.extend(current.mv);
+        }
+        moves
+    }
+}
+
+// Cursor navigation
+impl Game {
+
+    pub fn cursor(&self) -> NodeId {
+        self.cursor
+    }
+
+    pub fn current(&self) -> &Node {
+        self.node(self.cursor)
+    }
+
+    pub fn position(&self) -> Position {
+        self.current().position
+    }
+
+    ///Panics if `id` has been deleted.
+    pub fn go_to(&mut self, id: NodeId) {
+        assert!(self.get(id).is_some(), "node was deleted");
+        self.cursor = id;
+    }
+
+    ///Follows the main continuation. Returns false at the end of the line.
+    pub fn forward(&mut self) -> bool {
+        self.enter_variation(0)
+    }
+
+    ///Goes back one move. Returns false at the root.
+    pub fn back(&mut self) -> bool {
+        match self.current().parent {
+            None => false,
+            Some(parent) => {
+                self.cursor = parent;
+                true
+            }
+        }
+    }
+
+    ///Moves to the `index`-th child of the current node, 0 being the main
+    ///continuation. Returns false if there is no such child.
+    pub fn enter_variation(&mut self, index: usize) -> bool {
+        match self.current().children.get(index) {
+            None => false,
+            Some(&child) => {
+                self.cursor = child;
+                true
+            }
+        }
+    }
+
+    ///Goes back to the point where the current variation branched off the
+    ///main line of the game.
+    pub fn to_mainline(&mut self) {
+        while !self.is_mainline(self.cursor) {
+            self.back();
+        }
+    }
+
+    pub fn to_start(&mut self) {
+        self.cursor = self.root();
+    }
+
+    ///Follows the main continuation of the current line to its end.
+    pub fn to_end(&mut self) {
+        while self.forward() {}
+    }
+
+    ///A `Chessboard` for the cursor, with the history needed for repetition
+    ///and fifty move rule detection.
+    pub fn chessboard(&self) -> Chessboard {
+        let root = self.node(self.root()).position;
+        self.moves_to(self.cursor)
+            .into_iter()
+            .fold(Chessboard::new(root), |board, mv| board.make_move(mv))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normal(src: Square, dst: Square, piece: Piece) -> Move {
+        Move::Normal(src, dst, piece)
+    }
+
+    #[test]
+    fn variations_and_cursor() {
+        let mut game = Game::default();
+        let e4 = game.play(normal(Square::E2, Square::E4, Piece::Pawn)).unwrap();
+        let e5 = game.play(normal(Square::E7, Square::E5, Piece::Pawn)).unwrap();
+        game.back();
+        let c5 = game.play(normal(Square::C7, Square::C5, Piece::Pawn)).unwrap();
+
+        assert_eq!(game.node(e4).children(), &[e5, c5]);
+        assert!(game.is_mainline(e5));
+        assert!(!game.is_mainline(c5));
+
+        game.to_mainline();
+        assert_eq!(game.cursor(), e4);
+        game.forward();
+        assert_eq!(game.cursor(), e5);
+        game.back();
+        assert!(game.enter_variation(1));
+        assert_eq!(game.cursor(), c5);
+        assert!(!game.enter_variation(0));
+
+        game.to_start();
+        game.to_end();
+        assert_eq!(game.cursor(), e5);
+
+        // Playing a move that already exists reuses the node.
+        game.back();
+        assert_eq!(game.play(normal(Square::C7, Square::C5, Piece::Pawn)).unwrap(), c5);
+        assert!(game.play(normal(Square::E2, Square::E4, Piece::Pawn)).is_err());
+    }
+
+    #[test]
+    fn promote_demote_delete() {
+        let mut game = Game::default();
+        let root = game.root();
+        let e4 = game.add_move(root, normal(Square::E2, Square::E4, Piece::Pawn)).unwrap();
+        let d4 = game.add_move(root, normal(Square::D2, Square::D4, Piece::Pawn)).unwrap();
+        let c4 = game.add_move(root, normal(Square::C2, Square::C4, Piece::Pawn)).unwrap();
+        let d5 = game.add_move(d4, normal(Square::D7, Square::D5, Piece::Pawn)).unwrap();
+        let nf6 = game.add_move(d4, normal(Square::G8, Square::F6, Piece::Knight)).unwrap();
+
+        assert!(game.promote_variation(c4));
+        assert_eq!(game.node(root).children(), &[e4, c4, d4]);
+        assert!(game.demote_variation(c4));
+        assert!(!game.demote_variation(c4));
+        assert!(!game.promote_variation(e4));
+
+        game.promote_to_main(nf6);
+        assert_eq!(game.node(root).children(), &[d4, e4, c4]);
+        assert_eq!(game.mainline(), vec![normal(Square::D2, Square::D4, Piece::Pawn),
+                                         normal(Square::G8, Square::F6, Piece::Knight)]);
+        assert_eq!(game.moves_to(d5).len(), 2);
+
+        game.go_to(nf6);
+        game.delete_variation(d4);
+        assert_eq!(game.cursor(), root);
+        assert!(game.get(d5).is_none());
+        assert_eq!(game.node(root).children(), &[e4, c4]);
+    }
+
+    #[test]
+    fn annotations_and_chessboard() {
+        let mut game = Game::default();
+        let nf3 = game.play(normal(Square::G1, Square::F3, Piece::Knight)).unwrap();
+        game.node_mut(nf3).comments.push("A flexible start".to_owned());
+        game.node_mut(nf3).nags.push(1);
+        game.node_mut(nf3).clock = Some(Duration::from_secs(300));
+        for &mv in [normal(Square::G8, Square::F6, Piece::Knight),
+                    normal(Square::F3, Square::G1, Piece::Knight),
+                    normal(Square::F6, Square::G8, Piece::Knight),
+                    normal(Square::G1, Square::F3, Piece::Knight),
+                    normal(Square::G8, Square::F6, Piece::Knight),
+                    normal(Square::F3, Square::G1, Piece::Knight),
+                    normal(Square::F6, Square::G8, Piece::Knight)].iter() {
+            game.play(mv).unwrap();
+        }
+        assert_eq!(game.node(nf3).comments, vec!["A flexible start".to_owned()]);
+        assert_eq!(game.position(), Position::starting());
+        assert_eq!(game.chessboard().game_result(), Some(None));
+    }
+}
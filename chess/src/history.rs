@@ -0,0 +1,222 @@
+//!The positions a game went through, stored as a persistent stack: pushing
+//!shares everything below it, so cloning or forking a game is O(1) no
+//!matter how long it is.
+//!
+//!Every entry only keeps what repetition detection needs and the move that
+//!was played, the positions themselves are replayed from the first one
+//!when they are asked for.
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::position::Position;
+use crate::repr::Move;
+
+struct Entry {
+    hash: u64,
+    half_moves: u16,
+    ///Played from this position, `None` for a null move.
+    mv: Option<Move>,
+    previous: Option<Arc<Entry>>,
+}
+
+// The default drop would recurse once per entry and overflow the stack on
+// very long games, so the chain is unlinked iteratively instead.
+impl Drop for Entry {
+    fn drop(&mut self) {
+        let mut next = self.previous.take();
+        while let Some(entry) = next {
+            match Arc::try_unwrap(entry) {
+                Ok(mut entry) => next = entry.previous.take(),
+                // Still shared with another game, that one will drop it.
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct History {
+    ///The oldest position, the others are replayed from it.
+    first: Option<Arc<Position>>,
+    head: Option<Arc<Entry>>,
+    len: usize,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    ///A new history with `position` on top, left by playing `mv` (`None`
+    ///for a null move). `self` is left untouched.
+    pub fn push(&self, position: &Position, hash: u64, mv: Option<Move>) -> History {
+        let entry = Entry { hash, half_moves: position.half_moves, mv, previous: self.head.clone() };
+        History {
+            first: self.first.clone().or_else(|| Some(Arc::new(*position))),
+            head: Some(Arc::new(entry)),
+            len: self.len + 1,
+        }
+    }
+
+    ///The most recent position and the history before it. Replays the
+    ///whole game.
+    pub fn pop(&self) -> Option<(Position, History)> {
+        let head = self.head.as_ref()?;
+        let position = self.positions().next()?;
+        let first = if self.len > 1 { self.first.clone() } else { None };
+        Some((position, History { first, head: head.previous.clone(), len: self.len - 1 }))
+    }
+
+    ///Positions from the most recent to the oldest. Replays the whole game.
+    pub fn positions(&self) -> Positions {
+        let first = match &self.first {
+            Some(first) => **first,
+            None => return Positions { positions: Vec::new() },
+        };
+        let mut moves: Vec<Option<Move>> = self.entries().map(|entry| entry.mv).collect();
+        // The move played from the most recent position leads out of the
+        // history.
+        moves.remove(0);
+        let mut positions = Vec::with_capacity(self.len);
+        positions.push(first);
+        for mv in moves.into_iter().rev() {
+            let last = positions[positions.len() - 1];
+            positions.push(match mv {
+                Some(mv) => last.make_move(mv),
+                None => last.make_null_move(),
+            });
+        }
+        Positions { positions }
+    }
+
+    ///Hashes from the most recent to the oldest.
+    pub fn hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.entries().map(|entry| entry.hash)
+    }
+
+    ///Hashes from the most recent position back to the last capture or
+    ///pawn move, nothing before it can come again.
+    pub fn reversible_hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        let mut entries = self.entries();
+        let mut done = false;
+        core::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let entry = entries.next()?;
+            done = entry.half_moves == 0;
+            Some(entry.hash)
+        })
+    }
+
+    fn entries(&self) -> Entries<'_> {
+        Entries { entry: self.head.as_deref() }
+    }
+}
+
+struct Entries<'h> {
+    entry: Option<&'h Entry>,
+}
+
+impl<'h> Iterator for Entries<'h> {
+    type Item = &'h Entry;
+
+    fn next(&mut self) -> Option<&'h Entry> {
+        let entry = self.entry?;
+        self.entry = entry.previous.as_deref();
+        Some(entry)
+    }
+}
+
+pub struct Positions {
+    ///Oldest first, handed out from the end.
+    positions: Vec<Position>,
+}
+
+impl Iterator for Positions {
+    type Item = Position;
+
+    fn next(&mut self) -> Option<Position> {
+        self.positions.pop()
+    }
+}
+
+impl PartialEq for History {
+    fn eq(&self, other: &History) -> bool {
+        self.len == other.len && self.hashes().eq(other.hashes())
+    }
+}
+
+impl Eq for History {}
+
+impl Hash for History {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        self.hashes().next().hash(state);
+    }
+}
+
+impl fmt::Debug for History {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("History").field("len", &self.len).finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chessboard;
+    use crate::repr::*;
+
+    #[test]
+    fn push_and_pop() {
+        let start = Position::starting();
+        let e4 = Move::Normal(Square::E2, Square::E4, Piece::Pawn);
+        let after = start.make_move(e4);
+        let history = History::new().push(&start, start.zobrist(), Some(e4));
+        let forked = history.push(&after, after.zobrist(), None);
+        assert_eq!(history.len(), 1);
+        assert_eq!(forked.len(), 2);
+        assert_eq!(forked.positions().collect::<Vec<_>>(), [after, start]);
+
+        let (position, rest) = forked.pop().unwrap();
+        assert_eq!(position, after);
+        assert_eq!(rest, history);
+        let (position, rest) = rest.pop().unwrap();
+        assert_eq!(position, start);
+        assert!(rest.is_empty() && rest.positions().next().is_none());
+        assert!(History::new().pop().is_none());
+    }
+
+    #[test]
+    fn long_games_drop_without_overflow() {
+        let shuffle = [
+            Move::Normal(Square::G1, Square::F3, Piece::Knight),
+            Move::Normal(Square::G8, Square::F6, Piece::Knight),
+            Move::Normal(Square::F3, Square::G1, Piece::Knight),
+            Move::Normal(Square::F6, Square::G8, Piece::Knight),
+        ];
+        let mut board = Chessboard::starting();
+        let mut fork = None;
+        for i in 0..200_000 {
+            board = board.make_move(shuffle[i % 4]);
+            if i == 1000 {
+                fork = Some(board.clone());
+            }
+        }
+        assert_eq!(board.history().count(), 200_001);
+        drop(board);
+        assert_eq!(fork.unwrap().history().count(), 1002);
+    }
+}
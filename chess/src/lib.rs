@@ -1,33 +1,71 @@
-///This crate implements all of the rules of chess in an easy to use
-///and performant way (benchmarks will come later). I wrote this crate
-///for my very own chess engine.
+//!This crate implements all of the rules of chess in an easy to use
+//!and performant way (benchmarks will come later). I wrote this crate
+//!for my very own chess engine.
+//!
+//!The rules only need `core` and `alloc`. The default `std` feature adds
+//!terminal and SVG rendering and `std::error::Error` for [`ChessError`];
+//!without it the crate is `#![no_std]`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod position;
 pub mod constants;
 pub mod repr;
-
-use thiserror::Error;
-
+#[cfg(feature = "std")]
+pub mod svg;
+pub mod random;
+pub mod game;
+pub mod history;
+pub mod notation;
+pub mod builder;
+pub mod diagram;
+pub mod retro;
+
+use crate::history::{History, Positions};
 use crate::position::Position;
 use crate::repr::*;
-use std::sync::Arc;
-use std::str::FromStr;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
 
 
 
-#[derive(Error, Debug)]
+#[derive(Debug)]
 pub enum ChessError {
-    #[error("{0} is not a valid square")]
     ParseSquare(String),
-    #[error("{0}")]
     InvalidFEN(String),
-    #[error("{0} is not a valid move")]
     ParseMove(String),
-    #[error("{0} is not a legal move")]
     IllegalMove(Move),
-    #[error("this position has no previous")]
     NoPreviousPos,
+    InvalidMaterial(String),
+    InvalidPosition(builder::PositionError),
+    ParseDiagram(String),
+    ///No legal move leads to the position in this FEN.
+    NoMoveBetween(String),
 }
 
+impl fmt::Display for ChessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChessError::ParseSquare(square) => write!(f, "{} is not a valid square", square),
+            ChessError::InvalidFEN(reason) => write!(f, "{}", reason),
+            ChessError::ParseMove(mov) => write!(f, "{} is not a valid move", mov),
+            ChessError::IllegalMove(mov) => write!(f, "{} is not a legal move", mov),
+            ChessError::NoPreviousPos => write!(f, "this position has no previous"),
+            ChessError::InvalidMaterial(material) => write!(f, "{} is not a valid material signature", material),
+            ChessError::InvalidPosition(error) => write!(f, "invalid position: {}", error),
+            ChessError::ParseDiagram(reason) => write!(f, "invalid diagram: {}", reason),
+            ChessError::NoMoveBetween(fen) => write!(f, "no legal move leads to {}", fen),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChessError {}
+
 // #[derive(Debug, Copy, Clone)]
 // pub enum GameResult {
 //     WhiteWin,
@@ -38,20 +76,23 @@ pub enum ChessError {
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct Chessboard {
     pub position: Position,
-    pub previous: Option<Arc<Chessboard>>
+    hash: u64,
+    history: History,
 }
 
-pub struct HistoryIterator<'c> {
-    board: Option<&'c Chessboard>,
+pub struct HistoryIterator {
+    current: Option<Position>,
+    previous: Positions,
 }
 
-impl<'c> Iterator for HistoryIterator<'c> {
+impl Iterator for HistoryIterator {
     type Item = Position;
 
     fn next(&mut self) -> Option<Position> {
-        let position = self.board?.position;
-        self.board = self.board?.previous.as_ref().map(|p| &**p);
-        Some(position)
+        match self.current.take() {
+            Some(position) => Some(position),
+            None => self.previous.next(),
+        }
     }
 
 }
@@ -63,26 +104,21 @@ impl Chessboard {
     pub fn new(position: Position) -> Chessboard {
         Chessboard {
             position,
-            previous: None
+            hash: position.zobrist(),
+            history: History::new(),
         }
     }
 
     pub fn from_fen(fen: &str) -> Result<Chessboard, ChessError> {
-        Ok(Chessboard {
-            position: Position::from_fen(fen)?,
-            previous: None
-        })
+        Ok(Chessboard::new(Position::from_fen(fen)?))
     }
 
     pub fn starting() -> Chessboard {
-        Chessboard {
-            position: Position::starting(),
-            previous: None,
-        }
+        Chessboard::new(Position::starting())
     }
 
     pub fn at(&self, square: Square) -> Option<(Color, Piece)> {
-        self.position.board.at(square)
+        self.position.at(square)
     }
 
     pub fn legal_moves(&self) -> Vec<Move> {
@@ -90,14 +126,31 @@ impl Chessboard {
     }
 
     pub fn make_move(&self, legal_move: Move) -> Chessboard {
+        let position = self.position.make_move(legal_move);
+        Chessboard {
+            position,
+            hash: position.zobrist(),
+            history: self.history.push(&self.position, self.hash, Some(legal_move)),
+        }
+    }
+
+    ///See `Position::make_null_move`.
+    pub fn make_null_move(&self) -> Chessboard {
+        let position = self.position.make_null_move();
         Chessboard {
-            position: self.position.make_move(legal_move),
-            previous: Some(Arc::new(self.clone())),
+            position,
+            hash: position.zobrist(),
+            history: self.history.push(&self.position, self.hash, None),
         }
     }
 
+    ///Every board reachable in one move, with the move that leads there.
+    pub fn children(&self) -> impl ExactSizeIterator<Item = (Move, Chessboard)> + '_ {
+        self.position.legal_move_list().into_iter().map(move |mv| (mv, self.make_move(mv)))
+    }
+
     pub fn piece_at(&self, square: Square) -> Option<(Color,Piece)> {
-        self.position.board.at(square)
+        self.position.at(square)
     }
 
     pub fn move_is_valid(&self, mov: Move) -> bool {
@@ -106,18 +159,36 @@ impl Chessboard {
         legal_moves.contains(&mov)
     }
 
+    ///The current position followed by all the previous ones, most recent
+    ///first. The previous ones are replayed from the first.
     pub fn history(&self) -> HistoryIterator {
         HistoryIterator {
-            board: Some(&self)
+            current: Some(self.position),
+            previous: self.history.positions(),
         }
     }
-    
-    
+
+    ///Zobrist hash of the current position.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    ///How many times the current position occurred, counting this one.
+    ///Only the positions since the last capture or pawn move are looked at,
+    ///nothing before them can be a repetition.
+    pub fn repetitions(&self) -> usize {
+        if self.position.half_moves == 0 {
+            return 1;
+        }
+        1 + self.history.reversible_hashes()
+            .filter(|&hash| hash == self.hash)
+            .count()
+    }
 
     pub fn game_result(&self) -> Option<Option<Color>> {
 
         let legal_moves = self.legal_moves();
-        if legal_moves.len() == 0 {
+        if legal_moves.is_empty() {
             match self.position.in_check() {
                 true => return Some(Some(self.position.turn.other())),
                 false => return Some(None)
@@ -128,14 +199,8 @@ impl Chessboard {
             return Some(None)
         }
 
-        let mut count = 0;
-        for pos in self.history() {
-            if pos == self.position {
-                count += 1;
-            }
-            if count >= 3 {
-                return Some(None)
-            }
+        if self.repetitions() >= 3 {
+            return Some(None)
         }
         
         None
@@ -149,12 +214,15 @@ impl Chessboard {
         } 
     }
 
+    ///The board before the last move, replayed from the first position.
     pub fn previous(&self) -> Result<Chessboard, ChessError> {
-        match &self.previous {
+        match self.history.pop() {
             None => Err(ChessError::NoPreviousPos),
-            Some(board) => {
-                Ok((**board).clone())
-            }
+            Some((position, history)) => Ok(Chessboard {
+                position,
+                hash: position.zobrist(),
+                history,
+            }),
         }
     }
 
@@ -167,7 +235,7 @@ impl Chessboard {
         let dst = Square::from_str(&mov[2..4])?;
 
         if mov.len() == 5 {
-            let promotion = match mov.chars().collect::<Vec<char>>()[4] {
+            let promotion = match mov.chars().nth(4).unwrap() {
                 'q' | 'Q' => Piece::Queen,
                 'n' | 'N' => Piece::Knight,
                 'b' | 'B' => Piece::Bishop,
@@ -211,6 +279,31 @@ impl Chessboard {
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn threefold_repetition() {
+        use crate::Chessboard;
+        let board = Chessboard::starting()
+            .make_str_move("g1f3").unwrap()
+            .make_str_move("g8f6").unwrap()
+            .make_str_move("f3g1").unwrap()
+            .make_str_move("f6g8").unwrap();
+        assert_eq!(board.repetitions(), 2);
+        assert_eq!(board.game_result(), None);
+
+        let board = board
+            .make_str_move("g1f3").unwrap()
+            .make_str_move("g8f6").unwrap()
+            .make_str_move("f3g1").unwrap()
+            .make_str_move("f6g8").unwrap();
+        assert_eq!(board.repetitions(), 3);
+        assert_eq!(board.game_result(), Some(None));
+
+        // A pawn move makes everything before it unrepeatable.
+        let board = board.make_str_move("e2e4").unwrap();
+        assert_eq!(board.repetitions(), 1);
+        assert_eq!(board.previous().unwrap().repetitions(), 3);
+    }
+
     #[test]
     fn perft_starting2() {
         use crate::position::Position;
@@ -0,0 +1,415 @@
+//!Writing and reading moves in the usual notations. Every notation except
+//!UCI and ICCF depends on the position, for check suffixes and to
+//!disambiguate, so formatting and parsing both take one.
+use core::fmt::Write;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+
+use crate::position::Position;
+use crate::repr::*;
+use crate::ChessError;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Notation {
+    ///`e2e4`, `e7e8q`, `e1g1`, or `e1h1` for castling in Chess960 mode.
+    Uci,
+    ///Long algebraic: `Ng1-f3`, `Qd1xd7+`, `e7-e8=Q`.
+    Lan,
+    ///Standard algebraic: `Nf3`, `Qxd7+`, `exd8=Q#`.
+    San,
+    ///Standard algebraic with piece symbols instead of letters: `♘f3`.
+    Figurine,
+    ///ICCF numeric: `5254`, `5171` for castling, `57581` for promoting to a
+    ///queen.
+    Iccf,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MoveFormatter {
+    pub notation: Notation,
+    ///Write castling in UCI as the king taking its own rook, the way
+    ///Chess960 GUIs expect it.
+    pub chess960: bool,
+}
+
+impl Default for MoveFormatter {
+    fn default() -> MoveFormatter {
+        MoveFormatter::new(Notation::San)
+    }
+}
+
+impl MoveFormatter {
+    pub fn new(notation: Notation) -> MoveFormatter {
+        MoveFormatter { notation, chess960: false }
+    }
+
+    ///Formats the legal move `mv` played in `position`.
+    pub fn format(&self, position: &Position, mv: Move) -> String {
+        let mut text = String::new();
+        let player = position.turn;
+        let (from, to) = (mv.from(player), mv.to(player));
+
+        match self.notation {
+            Notation::Uci => {
+                let to = self.uci_destination(mv, player);
+                let _ = write!(text, "{}{}", from, to);
+                if let Some(piece) = mv.promotion() {
+                    text.push(piece_letter(piece).to_ascii_lowercase());
+                }
+            }
+            Notation::Iccf => {
+                // Castling is always written as the king's move.
+                for square in [from, to].iter() {
+                    let _ = write!(text, "{}{}", square.file() + 1, square.rank() + 1);
+                }
+                if let Some(piece) = mv.promotion() {
+                    let digit = match piece {
+                        Piece::Queen => '1',
+                        Piece::Rook => '2',
+                        Piece::Bishop => '3',
+                        _ => '4',
+                    };
+                    text.push(digit);
+                }
+            }
+            Notation::Lan => {
+                if !write_castle(&mut text, mv) {
+                    let piece = position.moved_piece(mv);
+                    if piece != Piece::Pawn {
+                        text.push(piece_letter(piece));
+                    }
+                    let separator = if position.is_capture(mv) { 'x' } else { '-' };
+                    let _ = write!(text, "{}{}{}", from, separator, to);
+                    if let Some(piece) = mv.promotion() {
+                        let _ = write!(text, "={}", piece_letter(piece));
+                    }
+                }
+                text.push_str(check_suffix(position, mv));
+            }
+            Notation::San | Notation::Figurine => {
+                let symbol = |piece: Piece| match self.notation {
+                    Notation::Figurine => piece.to_unicode(player),
+                    _ => piece_letter(piece),
+                };
+                if !write_castle(&mut text, mv) {
+                    let piece = position.moved_piece(mv);
+                    let capture = position.is_capture(mv);
+                    if piece == Piece::Pawn {
+                        if capture {
+                            text.push(File::from(from).to_char());
+                        }
+                    } else {
+                        text.push(symbol(piece));
+                        write_disambiguation(&mut text, position, mv, piece);
+                    }
+                    if capture {
+                        text.push('x');
+                    }
+                    let _ = write!(text, "{}", to);
+                    if let Some(piece) = mv.promotion() {
+                        let _ = write!(text, "={}", symbol(piece));
+                    }
+                }
+                text.push_str(check_suffix(position, mv));
+            }
+        }
+        text
+    }
+
+    ///Reads a move written in this formatter's notation. Only legal moves
+    ///are accepted. Check and annotation suffixes like `+`, `#` or `!?` are
+    ///ignored, as is the `=` before a promotion piece.
+    pub fn parse(&self, position: &Position, text: &str) -> Result<Move, ChessError> {
+        let invalid = || ChessError::ParseMove(text.to_owned());
+        let trimmed = text.trim().trim_end_matches(['+', '#', '!', '?']);
+
+        match self.notation {
+            Notation::Uci => {
+                let from: Square = trimmed.get(0..2).ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+                let to: Square = trimmed.get(2..4).ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+                let promotion = match trimmed.get(4..) {
+                    Some("") => None,
+                    Some(letter) => Some(parse_piece(letter).filter(|&p| p != Piece::King && p != Piece::Pawn)
+                                                           .ok_or_else(invalid)?),
+                    None => return Err(invalid()),
+                };
+                self.find_by_squares(position, from, to, promotion).ok_or_else(invalid)
+            }
+            Notation::Iccf => {
+                let digits: Option<alloc::vec::Vec<u8>> = trimmed.chars()
+                    .map(|c| c.to_digit(10).map(|d| d as u8))
+                    .collect();
+                let digits = digits.ok_or_else(invalid)?;
+                let square = |file: u8, rank: u8| match (file, rank) {
+                    (1..=8, 1..=8) => Some(Square::new(File::new(file - 1), Rank::new(rank - 1))),
+                    _ => None,
+                };
+                let (from, to, promotion) = match *digits.as_slice() {
+                    [f1, r1, f2, r2] => (square(f1, r1), square(f2, r2), None),
+                    [f1, r1, f2, r2, p] => {
+                        let piece = match p {
+                            1 => Piece::Queen,
+                            2 => Piece::Rook,
+                            3 => Piece::Bishop,
+                            4 => Piece::Knight,
+                            _ => return Err(invalid()),
+                        };
+                        (square(f1, r1), square(f2, r2), Some(piece))
+                    }
+                    _ => return Err(invalid()),
+                };
+                let (from, to) = (from.ok_or_else(invalid)?, to.ok_or_else(invalid)?);
+                self.find_by_squares(position, from, to, promotion).ok_or_else(invalid)
+            }
+            Notation::Lan => {
+                if let Some(castle) = parse_castle(trimmed) {
+                    return find(position, |mv| mv == castle).ok_or_else(invalid);
+                }
+                let (piece, rest) = split_piece(trimmed);
+                let (rest, promotion) = split_promotion(rest).ok_or_else(invalid)?;
+                let from: Square = rest.get(0..2).ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+                let to: Square = match rest.get(2..3) {
+                    Some("-") | Some("x") | Some(":") => rest.get(3..),
+                    _ => rest.get(2..),
+                }.ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+
+                find(position, |mv| !mv.is_castle() && mv.from(position.turn) == from && mv.to(position.turn) == to &&
+                                    mv.promotion() == promotion && position.moved_piece(mv) == piece)
+                    .ok_or_else(invalid)
+            }
+            Notation::San | Notation::Figurine => {
+                if let Some(castle) = parse_castle(trimmed) {
+                    return find(position, |mv| mv == castle).ok_or_else(invalid);
+                }
+                let (piece, rest) = split_piece(trimmed);
+                let (rest, promotion) = split_promotion(rest).ok_or_else(invalid)?;
+                let split = rest.len().checked_sub(2).ok_or_else(invalid)?;
+                let to: Square = rest.get(split..).ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+
+                let mut file = None;
+                let mut rank = None;
+                for c in rest[..split].chars() {
+                    match (File::from_char(c), Rank::from_char(c)) {
+                        (Some(f), _) if c.is_ascii_lowercase() => file = Some(f),
+                        (_, Some(r)) => rank = Some(r),
+                        _ if c == 'x' || c == ':' => {}
+                        _ => return Err(invalid()),
+                    }
+                }
+
+                let mut matches = position.legal_move_list().into_iter().filter(|&mv| {
+                    let from = mv.from(position.turn);
+                    !mv.is_castle() && mv.to(position.turn) == to && mv.promotion() == promotion &&
+                    position.moved_piece(mv) == piece &&
+                    file.is_none_or(|f| File::from(from) == f) &&
+                    rank.is_none_or(|r| Rank::from(from) == r)
+                });
+                match (matches.next(), matches.next()) {
+                    (Some(mv), None) => Ok(mv),
+                    _ => Err(invalid()),
+                }
+            }
+        }
+    }
+
+    ///Where the king goes in UCI: its own rook's square in Chess960 mode.
+    fn uci_destination(&self, mv: Move, player: Color) -> Square {
+        match mv.is_castle() && self.chess960 {
+            true => mv.castling_squares(player).2,
+            false => mv.to(player),
+        }
+    }
+
+    ///Castling is accepted both as the king's two-square step and as the
+    ///king taking its own rook.
+    fn find_by_squares(&self, position: &Position, from: Square, to: Square, promotion: Option<Piece>) -> Option<Move> {
+        let player = position.turn;
+        find(position, |mv| {
+            let destinations = match mv.is_castle() {
+                true => [mv.to(player), mv.castling_squares(player).2],
+                false => [mv.to(player); 2],
+            };
+            mv.from(player) == from && destinations.contains(&to) && mv.promotion() == promotion
+        })
+    }
+}
+
+fn find<F: Fn(Move) -> bool>(position: &Position, predicate: F) -> Option<Move> {
+    position.legal_move_list().into_iter().find(|&mv| predicate(mv))
+}
+
+fn piece_letter(piece: Piece) -> char {
+    piece.to_ascii().to_ascii_uppercase()
+}
+
+///Reads a piece from an English letter, in either case, or a figurine of
+///either color.
+fn parse_piece(text: &str) -> Option<Piece> {
+    let mut chars = text.chars();
+    let c = chars.next()?;
+    if chars.next().is_some() {
+        return None;
+    }
+    Piece::ALL.iter().copied().find(|&piece| {
+        c.to_ascii_uppercase() == piece_letter(piece) ||
+        c == piece.to_unicode(Color::White) || c == piece.to_unicode(Color::Black)
+    })
+}
+
+///Splits off a leading piece letter or figurine, pawns have none.
+fn split_piece(text: &str) -> (Piece, &str) {
+    match text.chars().next() {
+        Some(c) if c.is_uppercase() || !c.is_ascii() => {
+            match parse_piece(&text[..c.len_utf8()]) {
+                Some(piece) => (piece, &text[c.len_utf8()..]),
+                None => (Piece::Pawn, text),
+            }
+        }
+        _ => (Piece::Pawn, text),
+    }
+}
+
+///Splits off a trailing promotion like `=Q` or `Q`. `None` if the piece
+///isn't one a pawn can promote to.
+fn split_promotion(text: &str) -> Option<(&str, Option<Piece>)> {
+    let last = match text.chars().last() {
+        Some(c) if c.is_uppercase() || !c.is_ascii() => c,
+        _ => return Some((text, None)),
+    };
+    let piece = parse_piece(&text[text.len() - last.len_utf8()..])?;
+    if piece == Piece::Pawn || piece == Piece::King {
+        return None;
+    }
+    let rest = &text[..text.len() - last.len_utf8()];
+    Some((rest.strip_suffix('=').unwrap_or(rest), Some(piece)))
+}
+
+fn parse_castle(text: &str) -> Option<Move> {
+    match text {
+        "O-O" | "0-0" => Some(Move::KingsideCastle),
+        "O-O-O" | "0-0-0" => Some(Move::QueensideCastle),
+        _ => None,
+    }
+}
+
+///Writes `O-O` or `O-O-O` and returns whether `mv` was a castling move.
+fn write_castle(text: &mut String, mv: Move) -> bool {
+    match mv {
+        Move::KingsideCastle => text.push_str("O-O"),
+        Move::QueensideCastle => text.push_str("O-O-O"),
+        _ => return false,
+    }
+    true
+}
+
+fn check_suffix(position: &Position, mv: Move) -> &'static str {
+    let after = position.make_move(mv);
+    if after.checkers().is_empty() {
+        return "";
+    }
+    let mut has_moves = false;
+    after.for_each_legal(|_| has_moves = true);
+    if has_moves { "+" } else { "#" }
+}
+
+///The file, rank or both of the origin square, if another piece of the
+///same kind could also move to the destination.
+fn write_disambiguation(text: &mut String, position: &Position, mv: Move, piece: Piece) {
+    let player = position.turn;
+    let (from, to) = (mv.from(player), mv.to(player));
+
+    let mut others = Bitboard::empty();
+    position.for_each_legal(|other| {
+        if !other.is_castle() && other.to(player) == to && other.from(player) != from &&
+           position.moved_piece(other) == piece {
+            others.set(other.from(player));
+        }
+    });
+
+    if others.is_empty() {
+        return;
+    }
+    if (others & File::from(from).to_bitboard()).is_empty() {
+        text.push(File::from(from).to_char());
+    } else if (others & Rank::from(from).to_bitboard()).is_empty() {
+        text.push(Rank::from(from).to_char());
+    } else {
+        let _ = write!(text, "{}", from);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn formatted(notation: Notation, fen: &str, mv: Move) -> String {
+        let position = Position::from_fen(fen).unwrap();
+        MoveFormatter::new(notation).format(&position, mv)
+    }
+
+    #[test]
+    fn uci_castling() {
+        let position = Position::from_fen(KIWIPETE).unwrap();
+        let mut formatter = MoveFormatter::new(Notation::Uci);
+        assert_eq!(formatter.format(&position, Move::KingsideCastle), "e1g1");
+        assert_eq!(formatter.parse(&position, "e1h1").unwrap(), Move::KingsideCastle);
+        formatter.chess960 = true;
+        assert_eq!(formatter.format(&position, Move::QueensideCastle), "e1a1");
+        assert_eq!(formatter.parse(&position, "e1c1").unwrap(), Move::QueensideCastle);
+        assert!(formatter.parse(&position, "e1e3").is_err());
+        assert!(formatter.parse(&position, "e1").is_err());
+    }
+
+    #[test]
+    fn styles() {
+        let start = crate::constants::STARTING_POS_FEN;
+        let knight = Move::Normal(Square::G1, Square::F3, Piece::Knight);
+        assert_eq!(formatted(Notation::Lan, start, knight), "Ng1-f3");
+        assert_eq!(formatted(Notation::San, start, knight), "Nf3");
+        assert_eq!(formatted(Notation::Figurine, start, knight), "♘f3");
+        assert_eq!(formatted(Notation::Iccf, start, knight), "7163");
+
+        let fen = "3r2k1/5ppp/8/8/8/8/8/3QK3 w - - 0 1";
+        let queen = Move::Normal(Square::D1, Square::D8, Piece::Queen);
+        assert_eq!(formatted(Notation::Lan, fen, queen), "Qd1xd8#");
+        assert_eq!(formatted(Notation::San, fen, queen), "Qxd8#");
+
+        let fen = "1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        let promotion = Move::Promotion(Square::A7, Square::B8, Piece::Knight);
+        assert_eq!(formatted(Notation::San, fen, promotion), "axb8=N");
+        assert_eq!(formatted(Notation::Uci, fen, promotion), "a7b8n");
+        assert_eq!(formatted(Notation::Iccf, fen, promotion), "17284");
+        assert_eq!(formatted(Notation::Iccf, KIWIPETE, Move::KingsideCastle), "5171");
+    }
+
+    #[test]
+    fn disambiguation() {
+        let fen = "4k3/8/8/8/1N3N2/8/1N6/4K3 w - - 0 1";
+        assert_eq!(formatted(Notation::San, fen, Move::Normal(Square::F4, Square::D3, Piece::Knight)), "Nfd3");
+        assert_eq!(formatted(Notation::San, fen, Move::Normal(Square::B2, Square::D3, Piece::Knight)), "N2d3");
+        assert_eq!(formatted(Notation::San, fen, Move::Normal(Square::B4, Square::D3, Piece::Knight)), "Nb4d3");
+        assert_eq!(formatted(Notation::San, fen, Move::Normal(Square::F4, Square::H5, Piece::Knight)), "Nh5");
+    }
+
+    #[test]
+    fn every_style_round_trips() {
+        let styles = [Notation::Uci, Notation::Lan, Notation::San, Notation::Figurine, Notation::Iccf];
+        for fen in [KIWIPETE, "1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "8/8/8/k2pP2R/8/8/8/4K3 w - d6 0 1"].iter() {
+            let position = Position::from_fen(fen).unwrap();
+            for &notation in styles.iter() {
+                for &chess960 in [false, true].iter() {
+                    let formatter = MoveFormatter { notation, chess960 };
+                    let moves: Vec<Move> = position.legal_moves();
+                    for &mv in moves.iter() {
+                        let text = formatter.format(&position, mv);
+                        assert_eq!(formatter.parse(&position, &text).unwrap(), mv, "{:?} {}", notation, text);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,9 @@
-use std::hash::Hash;
-use std::collections::HashMap;
-use std::str::FromStr;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
 
+use crate::builder::PositionError;
 use crate::constants::*;
 use crate::repr::*;
 use crate::ChessError;
@@ -9,14 +11,22 @@ use crate::ChessError;
 
 type Board = Colored<Pieces>;
 
+///What stands on every square, indexed by `Square`.
+type Mailbox = [Option<(Color, Piece)>; 64];
 
-#[derive(Copy, Clone, Debug, Hash, Eq)]
+
+#[derive(Copy, Clone, Debug, Eq)]
 pub struct Position {
-    pub board: Board, 
+    ///Private so it can't get out of step with `mailbox`, build a new
+    ///position with `Position::new` to change it.
+    board: Board,
     pub turn: Color,
     pub castling_rights: Colored<CastlingRights>,
     pub en_passant: Option<Square>,
     pub half_moves: u16,
+    ///Starts at 1 and goes up after every black move.
+    pub full_moves: u16,
+    mailbox: Mailbox,
 }
 
 impl PartialEq for Position {
@@ -28,29 +38,34 @@ impl PartialEq for Position {
     }
 }
 
-fn intersects(lhs: Bitboard, rhs: Bitboard) -> bool {
-    lhs & rhs != Bitboard::empty()
+// Has to agree with `PartialEq`, so the move clocks are left out.
+impl Hash for Position {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.board.hash(state);
+        self.turn.hash(state);
+        self.castling_rights.hash(state);
+        self.en_passant.hash(state);
+    }
 }
 
-fn between(lhs: u8, between: u8, rhs: u8) -> bool {
-    std::cmp::min(lhs, rhs) < between && between < std::cmp::max(lhs,rhs)
+///One of the `*_slide` functions from `constants`.
+type Slide = fn(Square, Bitboard) -> (Bitboard, Bitboard);
+
+fn intersects(lhs: Bitboard, rhs: Bitboard) -> bool {
+    lhs & rhs != Bitboard::empty()
 }
 
 
 fn bishop_attack(occupied: Bitboard, square: Square) -> Bitboard {
-    let main_diag = MAIN_DIAG[square.main_diag() as usize];
-    let main_attacks = SLIDE_MAIN_DIAGONAL[&(square, main_diag & occupied)];
-    let second_diag = SECOND_DIAG[square.second_diag() as usize];
-    let second_attacks = SLIDE_SECOND_DIAGONAL[&(square, second_diag & occupied)]; 
+    let main_attacks = main_diagonal_slide(square, occupied);
+    let second_attacks = second_diagonal_slide(square, occupied);
 
     main_attacks.0 | second_attacks.0        
 }
 
 fn rook_attack(occupied: Bitboard, square: Square) -> Bitboard {
-    let rank = RANK[square.rank() as usize];
-    let horizontal_attacks = SLIDE_HORIZONTAL[&(square, rank & occupied)];
-    let file = FILE[square.file() as usize];
-    let vertical_attacks = SLIDE_VERTICAL[&(square, file & occupied)]; 
+    let horizontal_attacks = horizontal_slide(square, occupied);
+    let vertical_attacks = vertical_slide(square, occupied);
 
     horizontal_attacks.0 | vertical_attacks.0      
 }
@@ -90,42 +105,61 @@ impl Board {
         let mut attacked = Bitboard::empty();
 
         for knight in self[color][Piece::Knight].squares() {
-            attacked = attacked | knight_attack(knight);
+            attacked |= knight_attack(knight);
         }
         for bishop in self[color][Piece::Bishop].squares() {
-            attacked = attacked | bishop_attack(occupied, bishop);
+            attacked |= bishop_attack(occupied, bishop);
         }
         for rook in self[color][Piece::Rook].squares() {
-            attacked = attacked | rook_attack(occupied, rook);
+            attacked |= rook_attack(occupied, rook);
         }
         for queen in self[color][Piece::Queen].squares() {
-            attacked = attacked | queen_attack(occupied, queen);
+            attacked |= queen_attack(occupied, queen);
         }
         for pawn in self[color][Piece::Pawn].squares() {
-            attacked = attacked | pawn_attack(color, pawn);
+            attacked |= pawn_attack(color, pawn);
         }
         attacked
     }
 
+    ///Pieces of either color that attack `square`, with sliders seeing
+    ///through everything not in `occupied`.
+    pub fn attackers_to(&self, square: Square, occupied: Bitboard) -> Bitboard {
+        let rooks = self[Color::White][Piece::Rook] | self[Color::Black][Piece::Rook];
+        let bishops = self[Color::White][Piece::Bishop] | self[Color::Black][Piece::Bishop];
+        let queens = self[Color::White][Piece::Queen] | self[Color::Black][Piece::Queen];
+        let knights = self[Color::White][Piece::Knight] | self[Color::Black][Piece::Knight];
+        let kings = self[Color::White][Piece::King] | self[Color::Black][Piece::King];
+
+        // A pawn attacks the square if a pawn of the other color on the
+        // square would attack it.
+        pawn_attack(Color::Black, square) & self[Color::White][Piece::Pawn] |
+        pawn_attack(Color::White, square) & self[Color::Black][Piece::Pawn] |
+        knight_attack(square) & knights |
+        king_attack(square) & kings |
+        rook_attack(occupied, square) & (rooks | queens) |
+        bishop_attack(occupied, square) & (bishops | queens)
+    }
+
     pub fn in_check(&self, player: Color) -> bool {
         let opponent = player.other();
         let occupied = self.occupied();
         let mut attacked = Bitboard::empty();
 
         for knight in self[opponent][Piece::Knight].squares() {
-            attacked = attacked | knight_attack(knight);
+            attacked |= knight_attack(knight);
         }
         for bishop in self[opponent][Piece::Bishop].squares() {
-            attacked = attacked | bishop_attack(occupied, bishop);
+            attacked |= bishop_attack(occupied, bishop);
         }
         for rook in self[opponent][Piece::Rook].squares() {
-            attacked = attacked | rook_attack(occupied, rook);
+            attacked |= rook_attack(occupied, rook);
         }
         for queen in self[opponent][Piece::Queen].squares() {
-            attacked = attacked | queen_attack(occupied, queen);
+            attacked |= queen_attack(occupied, queen);
         }
         for pawn in self[opponent][Piece::Pawn].squares() {
-            attacked = attacked | pawn_attack(opponent, pawn);
+            attacked |= pawn_attack(opponent, pawn);
         }
 
         intersects(self[player][Piece::King], attacked)
@@ -146,19 +180,114 @@ impl Board {
 
 impl Position {
 
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    ///`board` has at most 16 pieces a side, so the moves fit in a
+    ///`MoveList`.
+    pub fn new(board: Board, turn: Color, castling_rights: Colored<CastlingRights>,
+               en_passant: Option<Square>, half_moves: u16) -> Position {
+        debug_assert!(board.0.occupied().count() <= 16 && board.1.occupied().count() <= 16);
+        let mut mailbox = [None; 64];
+        for &sq in Square::ALL.iter() {
+            mailbox[sq as usize] = board.at(sq);
+        }
+        Position { board, turn, castling_rights, en_passant, half_moves, full_moves: 1, mailbox }
+    }
+
+    ///The piece on `square`, without looking through the bitboards.
+    pub fn at(&self, square: Square) -> Option<(Color, Piece)> {
+        self.mailbox[square as usize]
+    }
+
     pub fn in_check(&self) -> bool {
-        return self.board.in_check(self.turn)
+        self.board.in_check(self.turn)
+    }
+
+    ///The piece making `mv`, the king for castling. Panics if there's no
+    ///piece of the side to move on the origin square.
+    pub fn moved_piece(&self, mv: Move) -> Piece {
+        match self.at(mv.from(self.turn)) {
+            Some((color, piece)) if color == self.turn => piece,
+            _ => panic!("no piece to move for {}", mv),
+        }
+    }
+
+    ///The piece `mv` takes, if any. En passant captures a pawn.
+    pub fn captured_piece(&self, mv: Move) -> Option<Piece> {
+        match mv {
+            Move::EnPassant(..) => Some(Piece::Pawn),
+            Move::KingsideCastle | Move::QueensideCastle => None,
+            Move::Normal(_, dst, _) | Move::Promotion(_, dst, _) => match self.at(dst) {
+                Some((color, piece)) if color != self.turn => Some(piece),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn is_capture(&self, mv: Move) -> bool {
+        self.captured_piece(mv).is_some()
+    }
+
+    ///Pieces of either color that attack `square` on the current board.
+    pub fn attackers_to(&self, square: Square) -> Bitboard {
+        self.board.attackers_to(square, self.board.occupied())
+    }
+
+    ///The opponent's pieces giving check to the side to move.
+    pub fn checkers(&self) -> Bitboard {
+        match self.board[self.turn][Piece::King].lsb() {
+            Some(king) => self.attackers_to(king) & self.board[self.turn.other()].occupied(),
+            None => Bitboard::empty(),
+        }
+    }
+
+    ///Whether the legal move `mv` puts the opponent in check.
+    pub fn gives_check(&self, mv: Move) -> bool {
+        !self.make_move(mv).checkers().is_empty()
+    }
+
+    ///Whether the legal move `mv` gives check with a piece other than the
+    ///one that moved, by opening a line to the king. For castling the rook
+    ///counts as the moving piece.
+    pub fn is_discovered_check(&self, mv: Move) -> bool {
+        let moved = match mv {
+            Move::KingsideCastle | Move::QueensideCastle => mv.castling_squares(self.turn).3,
+            _ => mv.to(self.turn),
+        };
+        !(self.make_move(mv).checkers() - moved.to_bitboard()).is_empty()
     }
 
     pub fn legal_moves(&self) -> Vec<Move> {
         let mut legal_moves = Vec::new();
+        self.for_each_legal(|mv| legal_moves.push(mv));
+        legal_moves
+    }
+
+    ///Like `legal_moves`, but into a list that doesn't allocate.
+    pub fn legal_move_list(&self) -> MoveList {
+        let mut legal_moves = MoveList::new();
+        self.for_each_legal(|mv| legal_moves.push(mv));
+        legal_moves
+    }
+
+    ///Every position reachable in one move, with the move that leads there.
+    pub fn children(&self) -> Children<'_> {
+        Children { position: self, moves: self.legal_move_list().into_iter() }
+    }
+
+    ///Calls `visit` with every legal move, in the same order as
+    ///`legal_moves`, without collecting them first.
+    pub fn for_each_legal<F: FnMut(Move)>(&self, mut visit: F) {
         
         let pieces = self.board;
         let player = self.turn;
         let opponent = player.other();
         let mut attacked = Bitboard::empty();
         
-        let mut pinned: HashMap<Square, Bitboard> = HashMap::new();
+        // The squares each piece may move to without exposing the king.
+        let mut pinned = [Bitboard::full(); 64];
         let mut nr_checks: u8 = 0;
         let mut protect_king = Bitboard::full();
 
@@ -167,7 +296,7 @@ impl Position {
 
         let king = pieces[player][Piece::King];
         let king_sq = match king.squares().next() {
-            None => return legal_moves,
+            None => return,
             Some(sq) => sq,
         };
 
@@ -179,7 +308,7 @@ impl Position {
                 nr_checks += 1;
                 protect_king = sq.to_bitboard();
             }
-            attacked = attacked | attacks;
+            attacked |= attacks;
         }
 
         //Opponent knights
@@ -189,86 +318,40 @@ impl Position {
                 nr_checks += 1;
                 protect_king = sq.to_bitboard();
             }
-            attacked = attacked | attacks;
+            attacked |= attacks;
         }
 
-        //Opponent rooks and queen;
-        for rook in pieces[opponent][Piece::Rook].squares().chain(pieces[opponent][Piece::Queen].squares()) {
-            let (h_attack, h_pin) = SLIDE_HORIZONTAL[&(rook, all_occupied & RANK[rook.rank() as usize])];
-
-            if intersects(h_attack,king) {
-                nr_checks += 1;
-                protect_king = h_attack & FILES_BETWEEN[rook.file() as usize][king_sq.file() as usize] | rook.to_bitboard(); 
-            }
-
-            if intersects(h_pin, king) {
-                for sq in (player_occupied & h_attack).squares() {
-                    if between(king_sq.file(), sq.file(), rook.file()) {
-                        pinned.insert(sq, RANK[sq.rank() as usize]);
+        //Opponent sliders, each line separately so pins can be found
+        let queens = pieces[opponent][Piece::Queen];
+        let sliders: [(Bitboard, [Slide; 2]); 2] = [
+            (pieces[opponent][Piece::Rook] | queens, [horizontal_slide, vertical_slide]),
+            (pieces[opponent][Piece::Bishop] | queens, [main_diagonal_slide, second_diagonal_slide]),
+        ];
+        for &(slider_pieces, slides) in sliders.iter() {
+            for slider in slider_pieces {
+                for slide in slides.iter() {
+                    let (attack, pin) = slide(slider, all_occupied);
+
+                    if intersects(attack, king) {
+                        nr_checks += 1;
+                        protect_king = between(slider, king_sq) | slider.to_bitboard();
                     }
-                }
-            }
-
-            let (v_attack, v_pin) = SLIDE_VERTICAL[&(rook, all_occupied & FILE[rook.file() as usize])];
-
-            if intersects(v_attack,king) {
-                nr_checks += 1;
-                protect_king = v_attack & RANKS_BETWEEN[rook.rank() as usize][king_sq.rank() as usize] | rook.to_bitboard(); 
-            }
 
-            if intersects(v_pin, king) {
-                let king_sq = king.squares().next().unwrap();
-                for sq in (player_occupied & v_attack).squares() {
-                    if between(king_sq.rank(), sq.rank(), rook.rank()) {
-                        pinned.insert(sq, FILE[sq.file() as usize]);
-                    }
-                }
-            }
-
-            attacked = attacked | h_attack | v_attack;
-        }
-        
-        //Opponent bishops and queen
-        for bishop in pieces[opponent][Piece::Bishop].squares().chain(pieces[opponent][Piece::Queen].squares()) {
-            let (md_attack, md_pin) = SLIDE_MAIN_DIAGONAL[&(bishop, all_occupied & MAIN_DIAG[bishop.main_diag() as usize])];
-
-            if intersects(md_attack,king) {
-                nr_checks += 1;
-                protect_king = md_attack & SECOND_DIAGS_BETWEEN[bishop.second_diag() as usize][king_sq.second_diag() as usize] | bishop.to_bitboard(); 
-            }
-
-            if intersects(md_pin, king) {
-                let king_sq = king.squares().next().unwrap();
-                for sq in (player_occupied & md_attack).squares() {
-                    if between(king_sq.second_diag(), sq.second_diag(), bishop.second_diag()) {
-                        pinned.insert(sq, MAIN_DIAG[sq.main_diag() as usize]);
+                    if intersects(pin, king) {
+                        for sq in player_occupied & attack & between(slider, king_sq) {
+                            pinned[sq as usize] = line(slider, king_sq);
+                        }
                     }
-                }
-            }
 
-            let (sd_attack, sd_pin) = SLIDE_SECOND_DIAGONAL[&(bishop, all_occupied & SECOND_DIAG[bishop.second_diag() as usize])];
-
-            if intersects(sd_attack,king) {
-                nr_checks += 1;
-                protect_king = sd_attack & MAIN_DIAGS_BETWEEN[bishop.main_diag() as usize][king_sq.main_diag() as usize] | bishop.to_bitboard(); 
-            }
-
-            if intersects(sd_pin, king) {
-                let king_sq = king.squares().next().unwrap();
-                for sq in (player_occupied & sd_attack).squares() {
-                    if between(king_sq.main_diag(), sq.main_diag(),  bishop.main_diag())  {
-                        pinned.insert(sq, SECOND_DIAG[sq.second_diag() as usize]);
-                    }
+                    attacked |= attack;
                 }
             }
-
-            attacked = attacked | md_attack | sd_attack;
         }
 
         //Opponent king {
         for sq in pieces[opponent][Piece::King].squares() {
             let attacks = king_attack(sq);
-            attacked = attacked | attacks;
+            attacked |= attacks;
         }
         
         all_occupied.set(king_sq);
@@ -277,137 +360,79 @@ impl Position {
         for king_sq in king.squares() {
             let move_sqs = king_attack(king_sq);
             for dest in (move_sqs & !attacked & !player_occupied).squares() {
-                legal_moves.push(Move::Normal(king_sq,dest, Piece::King));
+                visit(Move::Normal(king_sq,dest, Piece::King));
             }
         }
 
         if nr_checks > 1 {
-            return legal_moves
+            return
         }
 
         //Knight moves
         for knight in pieces[player][Piece::Knight].squares() {
             let jumps = knight_attack(knight);
-            let legal_squares = match pinned.get(&knight) {
-                None => jumps & protect_king & !player_occupied,
-                Some(&pin) => jumps & protect_king & !player_occupied & pin,
-            };
+            let legal_squares = jumps & protect_king & !player_occupied & pinned[knight as usize];
             for dest in legal_squares.squares() {
-                legal_moves.push(Move::Normal(knight, dest, Piece::Knight));
+                visit(Move::Normal(knight, dest, Piece::Knight));
             }
         }
 
         //Bishop moves
         for bishop in pieces[player][Piece::Bishop].squares() {
             let slides = bishop_attack(all_occupied, bishop);
-            let legal_squares = match pinned.get(&bishop) {
-                None => slides & protect_king & !player_occupied,
-                Some(&pin) => slides & protect_king & !player_occupied & pin,
-            };
+            let legal_squares = slides & protect_king & !player_occupied & pinned[bishop as usize];
             for dest in legal_squares.squares() {
-                legal_moves.push(Move::Normal(bishop, dest, Piece::Bishop));
+                visit(Move::Normal(bishop, dest, Piece::Bishop));
             }
         }
 
         //Rook moves
         for rook in pieces[player][Piece::Rook].squares() {
             let slides = rook_attack(all_occupied, rook);
-            let legal_squares = match pinned.get(&rook) {
-                None => slides & protect_king & !player_occupied,
-                Some(&pin) => slides & protect_king & !player_occupied & pin,
-            };
+            let legal_squares = slides & protect_king & !player_occupied & pinned[rook as usize];
             for dest in legal_squares.squares() {
-                legal_moves.push(Move::Normal(rook, dest, Piece::Rook));
+                visit(Move::Normal(rook, dest, Piece::Rook));
             }
         }
 
         //Queen moves
         for queen in pieces[player][Piece::Queen].squares() {
             let slides = queen_attack(all_occupied, queen);
-            let legal_squares = match pinned.get(&queen) {
-                None => slides & protect_king & !player_occupied,
-                Some(&pin) => slides & protect_king & !player_occupied & pin,
-            };
+            let legal_squares = slides & protect_king & !player_occupied & pinned[queen as usize];
             for dest in legal_squares.squares() {
-                legal_moves.push(Move::Normal(queen, dest, Piece::Queen));
+                visit(Move::Normal(queen, dest, Piece::Queen));
             }
         }
         
         //Pawn pushes
-        if player == Color::White {
-            let single_pushes = self.board[player][Piece::Pawn].0 << 8;
-            let single_pushes = single_pushes & !all_occupied.0;
-            let double_pushes = (single_pushes << 8) & RANK[3].0;
-
-            for dest in Bitboard(single_pushes).squares() {
-                let pawn = dest + Direction::Down;
-                let legal_squares = match pinned.get(&pawn) {
-                    None => protect_king & !all_occupied,
-                    Some(&pin) => protect_king & !all_occupied & pin,
-                };
-                if !intersects(legal_squares, dest.to_bitboard()) {
-                    continue;
-                }
-                if dest.rank() == 7 {
-                    legal_moves.push(Move::Promotion(pawn,dest, Piece::Queen));
-                    legal_moves.push(Move::Promotion(pawn,dest, Piece::Rook));
-                    legal_moves.push(Move::Promotion(pawn,dest, Piece::Knight));
-                    legal_moves.push(Move::Promotion(pawn,dest, Piece::Bishop));
-                } else {
-                    legal_moves.push(Move::Normal(pawn,dest, Piece::Pawn));
-                }
+        let forward = Direction::pawn(player);
+        let backward = forward.opposite();
+        let single_pushes = pieces[player][Piece::Pawn].shift(forward) - all_occupied;
+        let double_pushes = (single_pushes.shift(forward) - all_occupied) & Rank::Fourth.relative(player).to_bitboard();
+        let last_rank = Rank::Eighth.relative(player).to_bitboard();
+
+        for dest in single_pushes & protect_king {
+            let pawn = dest + backward;
+            if !pinned[pawn as usize].at(dest) {
+                continue;
             }
-
-            for dest in Bitboard(double_pushes).squares() {
-                let pawn = dest + Direction::Down + Direction::Down;
-                let legal_squares = match pinned.get(&pawn) {
-                    None => protect_king & !all_occupied,
-                    Some(&pin) => protect_king & !all_occupied & pin,
-                };
-                if !intersects(legal_squares, dest.to_bitboard()) {
-                    continue;
-                }
-                legal_moves.push(Move::Normal(pawn,dest, Piece::Pawn));
-            }
-            
-        } else {
-            let single_pushes = self.board[player][Piece::Pawn].0 >> 8;
-            let single_pushes = single_pushes & !all_occupied.0;
-            let double_pushes = (single_pushes >> 8) & RANK[4].0;
-
-            for dest in Bitboard(single_pushes).squares() {
-                let pawn = dest + Direction::Up;
-                let legal_squares = match pinned.get(&pawn) {
-                    None => protect_king & !all_occupied,
-                    Some(&pin) => protect_king & !all_occupied & pin,
-                };
-                if !intersects(legal_squares, dest.to_bitboard()) {
-                    continue;
-                }
-                if dest.rank() == 0 {
-                    legal_moves.push(Move::Promotion(pawn,dest, Piece::Queen));
-                    legal_moves.push(Move::Promotion(pawn,dest, Piece::Rook));
-                    legal_moves.push(Move::Promotion(pawn,dest, Piece::Knight));
-                    legal_moves.push(Move::Promotion(pawn,dest, Piece::Bishop));
-                } else {
-                    legal_moves.push(Move::Normal(pawn,dest, Piece::Pawn));
-                }
+            if last_rank.at(dest) {
+                visit(Move::Promotion(pawn,dest, Piece::Queen));
+                visit(Move::Promotion(pawn,dest, Piece::Rook));
+                visit(Move::Promotion(pawn,dest, Piece::Knight));
+                visit(Move::Promotion(pawn,dest, Piece::Bishop));
+            } else {
+                visit(Move::Normal(pawn,dest, Piece::Pawn));
             }
+        }
 
-            for dest in Bitboard(double_pushes).squares() {
-                let pawn = dest + Direction::Up + Direction::Up;
-                let legal_squares = match pinned.get(&pawn) {
-                    None => protect_king & !all_occupied,
-                    Some(&pin) => protect_king & !all_occupied & pin,
-                };
-                if !intersects(legal_squares, dest.to_bitboard()) {
-                    continue;
-                }
-                legal_moves.push(Move::Normal(pawn,dest, Piece::Pawn));
+        for dest in double_pushes & protect_king {
+            let pawn = dest + backward + backward;
+            if pinned[pawn as usize].at(dest) {
+                visit(Move::Normal(pawn,dest, Piece::Pawn));
             }
-
         }
-    
+
         let opponent_pieces = pieces[opponent].occupied();
         //Pawn attacks
         for pawn in self.board[player][Piece::Pawn].squares() {
@@ -416,10 +441,7 @@ impl Position {
                 None => Bitboard::empty(),
                 Some(sq) => sq.to_bitboard(),
             };
-            let legal_squares = match pinned.get(&pawn) {
-                None => protect_king & opponent_pieces | en_passant_bb,
-                Some(&pin) => protect_king & opponent_pieces & pin | en_passant_bb,
-            };
+            let legal_squares = protect_king & opponent_pieces & pinned[pawn as usize] | en_passant_bb;
 
             for dest in (attack & legal_squares).squares() {
                 if let Some(ep) = self.en_passant {
@@ -429,7 +451,7 @@ impl Position {
                         board[player][Piece::Pawn].set(dest);
                         board[opponent][Piece::Pawn].unset(ep + Direction::pawn(opponent));
                         if !board.in_check(player) {
-                            legal_moves.push(Move::EnPassant(pawn,dest));
+                            visit(Move::EnPassant(pawn,dest));
                         }
                         continue;
                     }
@@ -439,12 +461,12 @@ impl Position {
                     Color::Black => 0,
                 };
                 if dest.rank() == promote_rank { 
-                    legal_moves.push(Move::Promotion(pawn,dest,Piece::Queen));
-                    legal_moves.push(Move::Promotion(pawn,dest,Piece::Rook));
-                    legal_moves.push(Move::Promotion(pawn,dest,Piece::Bishop));
-                    legal_moves.push(Move::Promotion(pawn,dest,Piece::Knight));
+                    visit(Move::Promotion(pawn,dest,Piece::Queen));
+                    visit(Move::Promotion(pawn,dest,Piece::Rook));
+                    visit(Move::Promotion(pawn,dest,Piece::Bishop));
+                    visit(Move::Promotion(pawn,dest,Piece::Knight));
                 } else {
-                    legal_moves.push(Move::Normal(pawn,dest,Piece::Pawn));
+                    visit(Move::Normal(pawn,dest,Piece::Pawn));
                 }
             }
         }
@@ -453,17 +475,15 @@ impl Position {
         if self.castling_rights[player].kingside && 
            !intersects(SAFE_KING_CASTLE[player], attacked) &&
            !intersects(FREE_KING_CASTLE[player], all_occupied) {
-            legal_moves.push(Move::KingsideCastle);
+            visit(Move::KingsideCastle);
         }
 
         //Queenside Castle
         if self.castling_rights[player].queenside && 
            !intersects(SAFE_QUEEN_CASTLE[player], attacked) &&
            !intersects(FREE_QUEEN_CASTLE[player], all_occupied) {
-            legal_moves.push(Move::QueensideCastle);
+            visit(Move::QueensideCastle);
         }
-
-        legal_moves
     }
 
     pub fn make_move(&self, legal_move: Move) -> Position {
@@ -475,12 +495,11 @@ impl Position {
 
         match legal_move {
             Move::Normal(src,dst,piece) => {
-                position.board[player][piece].unset(src);
-                position.board[player][piece].set(dst);
-                if let Some(piece) = position.board[opponent].at(dst) {
-                    position.board[opponent][piece].unset(dst);
+                if position.remove(dst).is_some() {
                     reset_moves = true;
                 }
+                position.remove(src);
+                position.put(dst, player, piece);
                 if piece == Piece::Rook {
                     if src == Square::A1 || src == Square::A8 {
                         position.castling_rights[player].queenside = false; 
@@ -499,41 +518,24 @@ impl Position {
                 }
             },
             Move::EnPassant(src,dst) => {
-                position.board[player][Piece::Pawn].unset(src);
-                position.board[player][Piece::Pawn].set(dst);
-                position.board[opponent].unset(dst + Direction::pawn(opponent));
+                position.remove(src);
+                position.put(dst, player, Piece::Pawn);
+                position.remove(dst + Direction::pawn(opponent));
                 reset_moves = true;
             },
             Move::Promotion(src,dst,to) => {
-                position.board[player][Piece::Pawn].unset(src);
-                position.board[player][to].set(dst);
-                if let Some(piece) = position.board[opponent].at(dst) {
-                    position.board[opponent][piece].unset(dst);
+                if position.remove(dst).is_some() {
                     reset_moves = true;
                 }
+                position.remove(src);
+                position.put(dst, player, to);
             },
-            Move::KingsideCastle => {
-                let (old_king, old_rook, new_king, new_rook) = match player {
-                    Color::White => (Square::E1, Square::H1, Square::G1, Square::F1),
-                    Color::Black => (Square::E8, Square::H8, Square::G8, Square::F8),
-                };
-                position.board[player][Piece::King].unset(old_king);
-                position.board[player][Piece::King].set(new_king);
-                position.board[player][Piece::Rook].unset(old_rook);
-                position.board[player][Piece::Rook].set(new_rook);
-                
-                position.castling_rights[player].kingside = false;
-                position.castling_rights[player].queenside = false;
-            },
-            Move::QueensideCastle => {
-                let (old_king, old_rook, new_king, new_rook) = match player {
-                    Color::White => (Square::E1, Square::A1, Square::C1, Square::D1),
-                    Color::Black => (Square::E8, Square::A8, Square::C8, Square::D8),
-                };
-                position.board[player][Piece::King].unset(old_king);
-                position.board[player][Piece::King].set(new_king);
-                position.board[player][Piece::Rook].unset(old_rook);
-                position.board[player][Piece::Rook].set(new_rook);
+            Move::KingsideCastle | Move::QueensideCastle => {
+                let (old_king, new_king, old_rook, new_rook) = legal_move.castling_squares(player);
+                position.remove(old_king);
+                position.remove(old_rook);
+                position.put(new_king, player, Piece::King);
+                position.put(new_rook, player, Piece::Rook);
 
                 position.castling_rights[player].kingside = false;
                 position.castling_rights[player].queenside = false;
@@ -567,31 +569,193 @@ impl Position {
         if reset_moves {
             position.half_moves = 0;
         } else {
-            position.half_moves +=1;
+            position.half_moves = position.half_moves.saturating_add(1);
+        }
+        if player == Color::Black {
+            position.full_moves = position.full_moves.saturating_add(1);
         }
+        debug_assert!(position.mailbox_agrees(), "mailbox out of sync after {}", legal_move);
         position
     }
 
-    pub fn perft(&self, depth: u32) -> u64 {
-        let moves = self.legal_moves();
-        let mut nodes = 0;
+    ///Passes the turn without moving, the null move engines use to see if
+    ///a position is good even after giving the opponent a free move. The
+    ///side to move must not be in check.
+    pub fn make_null_move(&self) -> Position {
+        debug_assert!(!self.in_check(), "null move in check");
+        let mut position = *self;
+        position.en_passant = None;
+        position.half_moves = position.half_moves.saturating_add(1);
+        if position.turn == Color::Black {
+            position.full_moves = position.full_moves.saturating_add(1);
+        }
+        position.turn = position.turn.other();
+        position
+    }
+
+    ///Puts a piece on an empty square.
+    fn put(&mut self, square: Square, color: Color, piece: Piece) {
+        debug_assert!(self.mailbox[square as usize].is_none());
+        self.board[color][piece].set(square);
+        self.mailbox[square as usize] = Some((color, piece));
+    }
+
+    ///Empties a square and returns what was on it.
+    fn remove(&mut self, square: Square) -> Option<(Color, Piece)> {
+        let removed = self.mailbox[square as usize].take();
+        if let Some((color, piece)) = removed {
+            self.board[color][piece].unset(square);
+        }
+        removed
+    }
+
+    fn mailbox_agrees(&self) -> bool {
+        let mut board = Colored(Pieces::empty(), Pieces::empty());
+        for &sq in Square::ALL.iter() {
+            if let Some((color, piece)) = self.mailbox[sq as usize] {
+                board[color][piece].set(sq);
+            }
+        }
+        board == self.board
+    }
 
-        if depth == 0 {
-            return 1
+    ///Zobrist hash of everything `PartialEq` compares. Equal positions have
+    ///equal hashes, so this is what repetition detection works on.
+    pub fn zobrist(&self) -> u64 {
+        let mut hash = 0;
+        for &color in [Color::White, Color::Black].iter() {
+            for &piece in Piece::ALL.iter() {
+                for sq in self.board[color][piece].squares() {
+                    hash ^= ZOBRIST.pieces[color][piece as usize][sq as usize];
+                }
+            }
+            if self.castling_rights[color].kingside {
+                hash ^= ZOBRIST.castling[color][0];
+            }
+            if self.castling_rights[color].queenside {
+                hash ^= ZOBRIST.castling[color][1];
+            }
         }
-        if depth == 1 {
-            return moves.len() as u64
+        if let Some(sq) = self.en_passant {
+            hash ^= ZOBRIST.en_passant[sq.file() as usize];
+        }
+        if self.turn == Color::Black {
+            hash ^= ZOBRIST.black_to_move;
+        }
+        hash
+    }
+
+    ///The legal move that turns this position into `other`, ignoring the
+    ///move clocks. An en passant square nothing can capture on doesn't
+    ///count either, since many FENs leave it out.
+    pub fn move_between(&self, other: &Position) -> Option<Move> {
+        self.children().find(|(_, child)| child.same_as(other)).map(|(mv, _)| mv)
+    }
+
+    ///Whether the pieces, the side to move and the castling rights match,
+    ///and the en passant squares do where they can be captured on. The
+    ///clocks aren't compared.
+    pub(crate) fn same_as(&self, other: &Position) -> bool {
+        self.board == other.board &&
+        self.turn == other.turn &&
+        self.castling_rights == other.castling_rights &&
+        self.capturable_en_passant() == other.capturable_en_passant()
+    }
+
+    ///The en passant square if the side to move can capture there.
+    fn capturable_en_passant(&self) -> Option<Square> {
+        let capturable = self.legal_move_list().iter().any(|mv| matches!(mv, Move::EnPassant(..)));
+        self.en_passant.filter(|_| capturable)
+    }
+
+    ///The moves of a game given as consecutive FEN snapshots. Fails on the
+    ///first FEN that doesn't parse or isn't one legal move after the
+    ///previous one.
+    pub fn moves_between<S: AsRef<str>>(fens: &[S]) -> Result<Vec<Move>, ChessError> {
+        let positions = fens.iter()
+            .map(|fen| Position::from_fen(fen.as_ref()))
+            .collect::<Result<Vec<Position>, ChessError>>()?;
+
+        positions.windows(2).zip(fens.iter().skip(1)).map(|(pair, fen)| {
+            pair[0].move_between(&pair[1])
+                   .ok_or_else(|| ChessError::NoMoveBetween(fen.as_ref().to_owned()))
+        }).collect()
+    }
+
+    pub fn perft(&self, depth: u32) -> u64 {
+        match depth {
+            0 => 1,
+            1 => {
+                let mut nodes = 0;
+                self.for_each_legal(|_| nodes += 1);
+                nodes
+            }
+            _ => self.children().map(|(_, child)| child.perft(depth-1)).sum(),
         }
+    }
+}
+
+///Iterator returned by `Position::children`.
+pub struct Children<'p> {
+    position: &'p Position,
+    moves: move_list::IntoIter,
+}
+
+impl<'p> Iterator for Children<'p> {
+    type Item = (Move, Position);
+
+    fn next(&mut self) -> Option<(Move, Position)> {
+        let mv = self.moves.next()?;
+        Some((mv, self.position.make_move(mv)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.moves.size_hint()
+    }
+}
+
+impl<'p> ExactSizeIterator for Children<'p> {}
+
+// Symmetry transformations
+impl Position {
+
+    ///Swaps the two sides: white pieces become black pieces on the mirrored
+    ///rank and vice versa. Castling rights, en passant and the side to move
+    ///are swapped with them, so the result is the same position seen from
+    ///the other player's point of view.
+    pub fn flip_colors(&self) -> Position {
+        let flip = |pieces: Pieces| pieces.map(Bitboard::flip_vertical);
+
+        let mut flipped = Position::new(
+            Colored(flip(self.board[Color::Black]), flip(self.board[Color::White])),
+            self.turn.other(),
+            Colored(self.castling_rights[Color::Black], self.castling_rights[Color::White]),
+            self.en_passant.map(Square::flip_rank),
+            self.half_moves,
+        );
+        flipped.full_moves = self.full_moves;
+        flipped
+    }
 
-        for mv in moves.iter() {
-            let new_nodes = self.make_move(*mv).perft(depth-1);
-            // if depth == 2 {
-            //     println!("{}: {}", mv, new_nodes)
-            // }
-            nodes += new_nodes
+    ///Mirrors the board left to right. Castling isn't symmetric, so this
+    ///returns `None` if either side can still castle.
+    pub fn mirror_horizontal(&self) -> Option<Position> {
+        let no_castling = CastlingRights::new(false, false);
+        if self.castling_rights != Colored(no_castling, no_castling) {
+            return None;
         }
 
-        nodes
+        let flip = |pieces: Pieces| pieces.map(Bitboard::flip_horizontal);
+
+        let mut mirrored = Position::new(
+            Colored(flip(self.board[Color::White]), flip(self.board[Color::Black])),
+            self.turn,
+            self.castling_rights,
+            self.en_passant.map(Square::flip_file),
+            self.half_moves,
+        );
+        mirrored.full_moves = self.full_moves;
+        Some(mirrored)
     }
 }
 
@@ -604,6 +768,9 @@ impl Position {
      
     pub fn from_fen(fen: &str) -> Result<Position, ChessError> {
         let parts: Vec<&str> = fen.split_whitespace().collect();
+        if parts.len() < 4 {
+            return Err(ChessError::InvalidFEN("Missing FEN fields".to_owned()));
+        }
         
         let mut board = Colored(Pieces::empty(), Pieces::empty());
         let mut sq = Square::A8 as i8;
@@ -628,6 +795,12 @@ impl Position {
             sq += 1;
         }
 
+        for &color in [Color::White, Color::Black].iter() {
+            if board[color].occupied().count() > 16 || board[color][Piece::Pawn].count() > 8 {
+                return Err(ChessError::InvalidPosition(PositionError::TooManyPieces(color)));
+            }
+        }
+
         let turn = match parts[1] {
             "w" | "W" => Color::White,
             "b" | "B" => Color::Black,
@@ -655,31 +828,99 @@ impl Position {
 
         let en_passant = match parts[3] {
             "-" => None,
-            sq => Some(Square::from_str(sq).unwrap())
+            sq => Some(Square::from_str(sq).map_err(|_| ChessError::InvalidFEN("Invalid en passant FEN".to_owned()))?)
+        };
+
+        let half_moves = match parts.get(4) {
+            None => 0,
+            Some(n) => n.parse::<u16>().map_err(|_| ChessError::InvalidFEN("Invalid half moves FEN".to_owned()))?,
+        };
+        let full_moves = match parts.get(5) {
+            None => 1,
+            Some(n) => n.parse::<u16>().map_err(|_| ChessError::InvalidFEN("Invalid full moves FEN".to_owned()))?,
         };
 
-        let half_moves = str::parse::<u16>(parts[4]).unwrap();
+        let mut position = Position::new(board, turn, castling_rights, en_passant, half_moves);
+        position.full_moves = full_moves;
+        Ok(position)
+    }
+
+    pub fn to_fen(&self) -> alloc::string::String {
+        use core::fmt::Write;
+        let mut fen = alloc::string::String::new();
+
+        for rank in Rank::ALL.iter().rev() {
+            let mut empty = 0;
+            for &file in File::ALL.iter() {
+                match self.at(Square::new(file, *rank)) {
+                    None => empty += 1,
+                    Some((color, piece)) => {
+                        if empty > 0 {
+                            let _ = write!(fen, "{}", empty);
+                            empty = 0;
+                        }
+                        let letter = piece.to_ascii().to_ascii_uppercase();
+                        fen.push(match color {
+                            Color::White => letter,
+                            Color::Black => letter.to_ascii_lowercase(),
+                        });
+                    }
+                }
+            }
+            if empty > 0 {
+                let _ = write!(fen, "{}", empty);
+            }
+            if *rank != Rank::First {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(match self.turn {
+            Color::White => " w ",
+            Color::Black => " b ",
+        });
+
+        let rights = [
+            (self.castling_rights[Color::White].kingside, 'K'),
+            (self.castling_rights[Color::White].queenside, 'Q'),
+            (self.castling_rights[Color::Black].kingside, 'k'),
+            (self.castling_rights[Color::Black].queenside, 'q'),
+        ];
+        let before = fen.len();
+        for &(right, letter) in rights.iter() {
+            if right {
+                fen.push(letter);
+            }
+        }
+        if fen.len() == before {
+            fen.push('-');
+        }
 
-        Ok(Position { board, turn, castling_rights, en_passant, half_moves} )
+        match self.en_passant {
+            None => fen.push_str(" -"),
+            Some(sq) => { let _ = write!(fen, " {}", sq); }
+        }
+        let _ = write!(fen, " {} {}", self.half_moves, self.full_moves);
+        fen
     }
 
 
-    pub fn to_ascii(&self) -> String {
+    #[cfg(feature = "std")]
+    pub fn to_ascii(&self) -> alloc::string::String {
+        use alloc::string::{String, ToString};
         use colored::Colorize;
         let mut board: String = String::new();
         
         for row in (0..8u8).rev() {
             for col in 0..8u8 {
-                    let white_piece = self.board[Color::White].at(Square::from(row*8+col)); 
-                    let black_piece = self.board[Color::Black].at(Square::from(row*8+col)); 
-                    let sq = match (white_piece, black_piece) {
-                        (None, None) => ".".to_owned(),
-                        (Some(piece), None) => piece.to_ascii().to_string().cyan().to_string(),
-                        (None, Some(piece)) => piece.to_ascii().to_string().magenta().to_string(),
-                        _ => panic!("Impossible to have 2 pieces of different colors on the same square")
+                    // The case tells the colors apart when the colors are stripped.
+                    let sq = match self.at(Square::from(row*8+col)) {
+                        None => ".".to_owned(),
+                        Some((Color::White, piece)) => piece.to_ascii().to_ascii_uppercase().to_string().cyan().to_string(),
+                        Some((Color::Black, piece)) => piece.to_ascii().to_ascii_lowercase().to_string().magenta().to_string(),
                     };
                     board.push_str(&sq);
-                    board.push_str(" ");
+                    board.push(' ');
                 }
 
             board.push_str(&(row+1).to_string());
@@ -693,3 +934,166 @@ impl Position {
 
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn flip_colors_twice_is_identity() {
+        let position = Position::from_fen(KIWIPETE).unwrap();
+        assert_eq!(position.flip_colors().flip_colors(), position);
+        assert_ne!(position.flip_colors(), position);
+    }
+
+    #[test]
+    fn flip_colors_keeps_move_count() {
+        let position = Position::from_fen(KIWIPETE).unwrap();
+        let flipped = position.flip_colors();
+        assert_eq!(flipped.turn, Color::Black);
+        assert_eq!(flipped.at(Square::E8), Some((Color::Black, Piece::King)));
+        assert_eq!(flipped.perft(3), position.perft(3));
+    }
+
+    #[test]
+    fn flip_colors_en_passant() {
+        let position = Position::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let flipped = position.flip_colors();
+        assert_eq!(flipped.en_passant, Some(Square::E6));
+        assert_eq!(flipped.perft(2), position.perft(2));
+    }
+
+    #[test]
+    fn children_match_legal_moves() {
+        let position = Position::from_fen(KIWIPETE).unwrap();
+        let moves = position.legal_moves();
+        assert_eq!(position.legal_move_list().as_slice(), moves.as_slice());
+
+        let children = position.children();
+        assert_eq!(children.len(), 48);
+        for ((mv, child), &expected) in children.zip(moves.iter()) {
+            assert_eq!(mv, expected);
+            assert_eq!(child, position.make_move(mv));
+        }
+    }
+
+    #[test]
+    fn move_metadata() {
+        let position = Position::from_fen("4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1").unwrap();
+        let to_c3 = Move::Normal(Square::E4, Square::C3, Piece::Knight);
+        let to_f6 = Move::Normal(Square::E4, Square::F6, Piece::Knight);
+        let to_g5 = Move::Normal(Square::E4, Square::G5, Piece::Knight);
+        assert!(position.gives_check(to_c3) && position.is_discovered_check(to_c3));
+        assert!(position.gives_check(to_f6) && position.is_discovered_check(to_f6));
+        assert!(!position.gives_check(Move::Normal(Square::E1, Square::E2, Piece::Rook)));
+        assert_eq!(position.moved_piece(to_g5), Piece::Knight);
+        assert_eq!(position.captured_piece(to_g5), None);
+        assert_eq!(position.attackers_to(Square::C3), Square::E4.to_bitboard());
+
+        let position = Position::from_fen("8/8/8/k2pP2R/8/8/8/4K3 w - d6 0 1").unwrap();
+        let en_passant = Move::EnPassant(Square::E5, Square::D6);
+        assert_eq!(position.captured_piece(en_passant), Some(Piece::Pawn));
+        assert!(position.is_discovered_check(en_passant));
+
+        let position = Position::from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(position.gives_check(Move::KingsideCastle));
+        assert!(!position.is_discovered_check(Move::KingsideCastle));
+        assert_eq!(position.moved_piece(Move::KingsideCastle), Piece::King);
+        assert_eq!(Move::KingsideCastle.from(Color::White), Square::E1);
+        assert_eq!(Move::QueensideCastle.to(Color::Black), Square::C8);
+
+        let promotion = Move::Promotion(Square::B7, Square::A8, Piece::Knight);
+        let position = Position::from_fen("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(promotion.is_promotion() && !to_c3.is_promotion());
+        assert_eq!(position.captured_piece(promotion), Some(Piece::Rook));
+        assert_eq!(position.moved_piece(promotion), Piece::Pawn);
+    }
+
+    #[test]
+    fn moves_between_positions() {
+        let position = Position::from_fen(KIWIPETE).unwrap();
+        for (mv, child) in position.children() {
+            assert_eq!(position.move_between(&child), Some(mv));
+        }
+        assert_eq!(position.move_between(&position), None);
+
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+        ];
+        assert_eq!(Position::moves_between(&fens).unwrap(), vec![
+            Move::Normal(Square::E2, Square::E4, Piece::Pawn),
+            Move::Normal(Square::E7, Square::E5, Piece::Pawn),
+        ]);
+        assert!(matches!(Position::moves_between(&[fens[0], fens[2]]), Err(ChessError::NoMoveBetween(_))));
+        assert!(Position::moves_between(&[fens[0], "not a fen"]).is_err());
+
+        // 1.e4 written with and without the en passant square.
+        let e4 = Move::Normal(Square::E2, Square::E4, Piece::Pawn);
+        for &after in ["e3", "-"].iter() {
+            let fen = format!("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq {} 0 1", after);
+            assert_eq!(Position::moves_between(&[fens[0], &fen]).unwrap(), vec![e4]);
+        }
+        // Leaving it out when it can be captured is a different position.
+        let before = "rnbqkbnr/ppp1pppp/8/8/3p4/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert!(Position::moves_between(&[before, "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"]).is_ok());
+        assert!(Position::moves_between(&[before, "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"]).is_err());
+    }
+
+    #[test]
+    fn move_list_capacity() {
+        // 279 legal moves, more than any 16 pieces can have.
+        let crowded = "QQQQQQQk/Q6Q/Q6Q/Q3Q2Q/Q6Q/Q6Q/Q6Q/KQQQQQQQ w - - 0 1";
+        assert!(matches!(Position::from_fen(crowded),
+                         Err(ChessError::InvalidPosition(PositionError::TooManyPieces(Color::White)))));
+        assert!(Position::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/PPPPK3 w - - 0 1").is_err());
+
+        // Sixteen pieces with plenty of room.
+        let position = Position::from_fen("1Q4Qk/Q3Q3/Q1Q4Q/Q4Q2/3Q4/1Q4Q1/4Q3/K1Q4Q w - - 0 1").unwrap();
+        assert_eq!(position.legal_move_list().len(), position.legal_moves().len());
+        assert!(position.legal_moves().len() > 218);
+    }
+
+    #[test]
+    fn mailbox_follows_moves() {
+        let position = Position::from_fen("r3k3/1P6/8/3pP3/8/8/8/R3K2R w KQq d6 0 1").unwrap();
+        assert_eq!(position.at(Square::D5), Some((Color::Black, Piece::Pawn)));
+
+        let position = position.make_move(Move::EnPassant(Square::E5, Square::D6));
+        assert_eq!(position.at(Square::D5), None);
+        assert_eq!(position.at(Square::D6), Some((Color::White, Piece::Pawn)));
+
+        let position = position.make_move(Move::Normal(Square::A8, Square::A1, Piece::Rook));
+        assert_eq!(position.at(Square::A1), Some((Color::Black, Piece::Rook)));
+
+        let position = position.make_move(Move::KingsideCastle);
+        assert_eq!(position.at(Square::G1), Some((Color::White, Piece::King)));
+        assert_eq!(position.at(Square::F1), Some((Color::White, Piece::Rook)));
+        assert_eq!(position.at(Square::H1), None);
+        assert!(position.mailbox_agrees());
+    }
+
+    #[test]
+    fn null_move() {
+        let position = Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let passed = position.make_null_move();
+        assert_eq!(passed.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 b - - 1 1");
+        assert_ne!(passed.zobrist(), position.zobrist());
+        assert_eq!(passed.make_null_move().to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 w - - 2 2");
+    }
+
+    #[test]
+    fn mirror_horizontal() {
+        let position = Position::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        let mirrored = position.mirror_horizontal().unwrap();
+        assert_eq!(mirrored.at(Square::H5), Some((Color::White, Piece::King)));
+        assert_eq!(mirrored.mirror_horizontal(), Some(position));
+        assert_eq!(mirrored.perft(3), position.perft(3));
+
+        assert_eq!(Position::starting().mirror_horizontal(), None);
+    }
+}
@@ -0,0 +1,317 @@
+//!Seeded generators for random games and positions, mostly useful for
+//!fuzzing the move generator and producing training data.
+use core::fmt;
+use core::str::FromStr;
+use alloc::borrow::ToOwned;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::constants::*;
+use crate::position::Position;
+use crate::repr::*;
+use crate::{ChessError, Chessboard};
+
+///How many times `random_position` retries before it gives up on a
+///signature that is (nearly) impossible to place legally.
+const MAX_ATTEMPTS: u32 = 10_000;
+
+///A small splitmix64 generator. It's not cryptographically secure, it only
+///needs to be fast and reproducible from a seed.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    ///A number in `0..bound`. `bound` must not be 0.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        match items.len() {
+            0 => None,
+            n => Some(&items[self.below(n as u64) as usize]),
+        }
+    }
+}
+
+///The pieces each side has, e.g. `KRPvKR`. Kings are mandatory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MaterialSignature {
+    pub counts: Colored<[u8; 6]>,
+}
+
+impl MaterialSignature {
+    pub fn count(&self, color: Color, piece: Piece) -> u8 {
+        self.counts[color][piece as usize]
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.0.iter().chain(self.counts.1.iter()).map(|&n| n as u32).sum()
+    }
+
+    ///Whether a position can have this material: one king, at most 8 pawns
+    ///and at most 16 pieces a side.
+    pub fn is_possible(&self) -> bool {
+        [Color::White, Color::Black].iter().all(|&color| {
+            let counts = &self.counts[color];
+            counts[Piece::King as usize] == 1 &&
+            counts[Piece::Pawn as usize] <= 8 &&
+            counts.iter().map(|&n| n as u32).sum::<u32>() <= 16
+        })
+    }
+}
+
+impl FromStr for MaterialSignature {
+    type Err = ChessError;
+
+    fn from_str(signature: &str) -> Result<MaterialSignature, ChessError> {
+        let invalid = || ChessError::InvalidMaterial(signature.to_owned());
+
+        let mut sides = signature.split(['v', 'V']);
+        let (white, black) = match (sides.next(), sides.next(), sides.next()) {
+            (Some(white), Some(black), None) => (white, black),
+            _ => return Err(invalid()),
+        };
+
+        let mut counts = Colored([0u8; 6], [0u8; 6]);
+        for (color, side) in [(Color::White, white), (Color::Black, black)].iter() {
+            // Also keeps the counts from overflowing.
+            if side.chars().count() > 16 {
+                return Err(invalid());
+            }
+            for c in side.chars() {
+                let piece = match c.to_ascii_uppercase() {
+                    'P' => Piece::Pawn,
+                    'N' => Piece::Knight,
+                    'B' => Piece::Bishop,
+                    'R' => Piece::Rook,
+                    'Q' => Piece::Queen,
+                    'K' => Piece::King,
+                    _ => return Err(invalid()),
+                };
+                counts[*color][piece as usize] += 1;
+            }
+        }
+
+        let material = MaterialSignature { counts };
+        match material.is_possible() {
+            true => Ok(material),
+            false => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for MaterialSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Conventional order: king first, then from the queen down.
+        let order = [Piece::King, Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight, Piece::Pawn];
+        for (i, &color) in [Color::White, Color::Black].iter().enumerate() {
+            if i == 1 {
+                write!(f, "v")?;
+            }
+            for &piece in order.iter() {
+                for _ in 0..self.count(color, piece) {
+                    write!(f, "{}", piece.to_ascii().to_ascii_uppercase())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Generator {
+    pub rng: Rng,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Generator {
+        Generator { rng: Rng::new(seed) }
+    }
+
+    ///Plays uniformly random legal moves from the starting position until
+    ///the game ends or `max_plies` moves have been made.
+    pub fn random_game(&mut self, max_plies: usize) -> Chessboard {
+        self.weighted_game(Chessboard::starting(), max_plies, |_, _| 1)
+    }
+
+    ///Plays random legal moves from `chessboard`, picking each move with a
+    ///probability proportional to `weight`. A weight of 0 means the move is
+    ///never picked, unless every move has weight 0.
+    pub fn weighted_game<F>(&mut self, mut chessboard: Chessboard, max_plies: usize, mut weight: F) -> Chessboard
+    where F: FnMut(&Position, Move) -> u32 {
+        for _ in 0..max_plies {
+            if chessboard.game_result().is_some() {
+                break;
+            }
+            let moves = chessboard.legal_moves();
+            let weights: Vec<u64> = moves.iter().map(|&mv| weight(&chessboard.position, mv) as u64).collect();
+            let total: u64 = weights.iter().sum();
+
+            let mv = match total {
+                0 => *self.rng.choose(&moves).unwrap(),
+                _ => {
+                    let mut pick = self.rng.below(total);
+                    let mut chosen = moves[0];
+                    for (&mv, &w) in moves.iter().zip(weights.iter()) {
+                        if pick < w {
+                            chosen = mv;
+                            break;
+                        }
+                        pick -= w;
+                    }
+                    chosen
+                }
+            };
+            chessboard = chessboard.make_move(mv);
+        }
+        chessboard
+    }
+
+    ///A random legal position with exactly the given material. Kings are
+    ///never adjacent, pawns are never on the first or last rank and the side
+    ///that isn't to move is never in check. Neither side can castle.
+    pub fn random_position(&mut self, material: &MaterialSignature, turn: Color) -> Result<Position, ChessError> {
+        if !material.is_possible() {
+            return Err(ChessError::InvalidMaterial(material.to_string()));
+        }
+
+        'attempts: for _ in 0..MAX_ATTEMPTS {
+            let mut board = Colored(Pieces::empty(), Pieces::empty());
+            let mut occupied = Bitboard::empty();
+
+            let white_king = self.random_square(occupied);
+            occupied.set(white_king);
+            board[Color::White][Piece::King].set(white_king);
+
+            let black_king = self.random_square(occupied);
+            if (white_king.file() as i8 - black_king.file() as i8).abs() <= 1 &&
+               (white_king.rank() as i8 - black_king.rank() as i8).abs() <= 1 {
+                continue;
+            }
+            occupied.set(black_king);
+            board[Color::Black][Piece::King].set(black_king);
+
+            for &color in [Color::White, Color::Black].iter() {
+                for &piece in Piece::ALL.iter().filter(|&&piece| piece != Piece::King) {
+                    for _ in 0..material.count(color, piece) {
+                        let allowed = match piece {
+                            Piece::Pawn => !occupied & !(RANK[0] | RANK[7]),
+                            _ => !occupied,
+                        };
+                        if allowed == Bitboard::empty() {
+                            continue 'attempts;
+                        }
+                        let square = self.random_square(!allowed);
+                        occupied.set(square);
+                        board[color][piece].set(square);
+                    }
+                }
+            }
+
+            if board.in_check(turn.other()) {
+                continue;
+            }
+
+            let no_castling = CastlingRights::new(false, false);
+            return Ok(Position::new(board, turn, Colored(no_castling, no_castling), None, 0));
+        }
+
+        Err(ChessError::InvalidMaterial(material.to_string()))
+    }
+
+    ///A uniformly random square that isn't in `taken`.
+    fn random_square(&mut self, taken: Bitboard) -> Square {
+        let free = (!taken).count() as u64;
+        let n = self.rng.below(free) as usize;
+        (!taken).squares().nth(n).unwrap()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_game() {
+        let first = Generator::new(7).random_game(200);
+        let second = Generator::new(7).random_game(200);
+        let first: Vec<Position> = first.history().collect();
+        let second: Vec<Position> = second.history().collect();
+        assert_eq!(first, second);
+        assert!(first.len() > 1);
+    }
+
+    #[test]
+    fn weighted_game_follows_weights() {
+        let mut generator = Generator::new(1);
+        let pawn_moves_only = |position: &Position, mv: Move| match mv {
+            Move::Normal(src, _, _) => (position.at(src) == Some((position.turn, Piece::Pawn))) as u32,
+            _ => 0,
+        };
+        let board = generator.weighted_game(Chessboard::starting(), 6, pawn_moves_only);
+        let position = board.position;
+        assert_eq!(position.board()[Color::White][Piece::Knight], Position::starting().board()[Color::White][Piece::Knight]);
+        assert_eq!(position.board()[Color::Black][Piece::Knight], Position::starting().board()[Color::Black][Piece::Knight]);
+    }
+
+    #[test]
+    fn material_signature() {
+        let material: MaterialSignature = "KRPvKR".parse().unwrap();
+        assert_eq!(material.count(Color::White, Piece::Rook), 1);
+        assert_eq!(material.count(Color::White, Piece::Pawn), 1);
+        assert_eq!(material.count(Color::Black, Piece::Pawn), 0);
+        assert_eq!(material.to_string(), "KRPvKR");
+
+        assert!("KRPKR".parse::<MaterialSignature>().is_err());
+        assert!("KKvK".parse::<MaterialSignature>().is_err());
+        assert!("KXvK".parse::<MaterialSignature>().is_err());
+        assert!("KQQQQQQQQQQQQQQQQvK".parse::<MaterialSignature>().is_err());
+        assert!(format!("{}vK", "Q".repeat(300)).parse::<MaterialSignature>().is_err());
+
+        let mut crowded: MaterialSignature = "KvK".parse().unwrap();
+        crowded.counts[Color::White][Piece::Queen as usize] = 16;
+        assert!(!crowded.is_possible());
+        assert!(Generator::new(1).random_position(&crowded, Color::White).is_err());
+    }
+
+    #[test]
+    fn random_positions_are_legal() {
+        let mut generator = Generator::new(42);
+        for signature in ["KRPvKR", "KQvK", "KPPPvKNB", "KQRRBBNNPPPPPPPPvKQRRBBNNPPPPPPPP"].iter() {
+            let material: MaterialSignature = signature.parse().unwrap();
+            for &turn in [Color::White, Color::Black].iter() {
+                for _ in 0..20 {
+                    let position = generator.random_position(&material, turn).unwrap();
+                    assert_eq!(position.turn, turn);
+                    assert!(!position.board().in_check(turn.other()));
+                    for &color in [Color::White, Color::Black].iter() {
+                        for &piece in Piece::ALL.iter() {
+                            assert_eq!(position.board()[color][piece].count(), material.count(color, piece) as u32);
+                        }
+                        let pawns = position.board()[color][Piece::Pawn];
+                        assert!(pawns.squares().all(|sq| sq.rank() != 0 && sq.rank() != 7));
+                    }
+                    let white_king = position.board()[Color::White][Piece::King].squares().next().unwrap();
+                    let black_king = position.board()[Color::Black][Piece::King].squares().next().unwrap();
+                    assert!(!KING_ATTACKS[white_king as usize].at(black_king));
+                }
+            }
+        }
+    }
+}
@@ -1,13 +1,14 @@
-use std::iter::Iterator;
-use std::ops::{Add, Deref, DerefMut, BitOr, BitAnd, Not};
-use std::str::FromStr;
-use std::fmt::Display;
-use strum_macros::EnumIter;
+use core::iter::{FromIterator, Iterator};
+use core::ops::{Add, Deref, DerefMut, BitOr, BitAnd, BitXor, Not, Sub, Shl};
+use core::ops::{BitOrAssign, BitAndAssign, BitXorAssign};
+use core::str::FromStr;
+use core::fmt::Display;
+use alloc::borrow::ToOwned;
 use super::Color;
 use crate::ChessError;
 
 #[repr(u8)]
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Ord, PartialOrd, EnumIter)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Ord, PartialOrd)]
 pub enum Square {
     A1, B1, C1, D1, E1, F1, G1, H1,
     A2, B2, C2, D2, E2, F2, G2, H2,
@@ -22,6 +23,21 @@ pub enum Square {
 
 
 impl Square {
+    pub const ALL: [Square; 64] = [
+        Square::A1, Square::B1, Square::C1, Square::D1, Square::E1, Square::F1, Square::G1, Square::H1,
+        Square::A2, Square::B2, Square::C2, Square::D2, Square::E2, Square::F2, Square::G2, Square::H2,
+        Square::A3, Square::B3, Square::C3, Square::D3, Square::E3, Square::F3, Square::G3, Square::H3,
+        Square::A4, Square::B4, Square::C4, Square::D4, Square::E4, Square::F4, Square::G4, Square::H4,
+        Square::A5, Square::B5, Square::C5, Square::D5, Square::E5, Square::F5, Square::G5, Square::H5,
+        Square::A6, Square::B6, Square::C6, Square::D6, Square::E6, Square::F6, Square::G6, Square::H6,
+        Square::A7, Square::B7, Square::C7, Square::D7, Square::E7, Square::F7, Square::G7, Square::H7,
+        Square::A8, Square::B8, Square::C8, Square::D8, Square::E8, Square::F8, Square::G8, Square::H8,
+    ];
+
+    pub fn new(file: File, rank: Rank) -> Square {
+        Square::from(rank as u8 * 8 + file as u8)
+    }
+
     pub fn to_bitboard(self) -> Bitboard {
         let sq = self as u8;
         Bitboard(1 << (8 * (sq/8) +  (sq%8)))
@@ -46,6 +62,16 @@ impl Square {
     pub fn ray(self, direction: Direction) -> Ray {
         Ray { square: self as i8, direction}
     }
+
+    ///The same file on the opposite rank, e.g. `c2` becomes `c7`.
+    pub fn flip_rank(self) -> Square {
+        Square::from(self as u8 ^ 56)
+    }
+
+    ///The same rank on the opposite file, e.g. `c2` becomes `f2`.
+    pub fn flip_file(self) -> Square {
+        Square::from(self as u8 ^ 7)
+    }
 }
 
 
@@ -53,27 +79,102 @@ impl FromStr for Square {
     type Err = ChessError;
 
     fn from_str(string: &str) -> Result<Square, ChessError> {
-        if string.len() != 2 {
-            return Err(ChessError::ParseSquare(string.to_owned()));
-        }
-        
         let mut chars = string.chars();
-        let file = chars.next().unwrap().to_ascii_lowercase();
-        let rank = chars.next().unwrap();
+        let file = chars.next().and_then(File::from_char);
+        let rank = chars.next().and_then(Rank::from_char);
+
+        match (file, rank, chars.next()) {
+            (Some(file), Some(rank), None) => Ok(Square::new(file, rank)),
+            _ => Err(ChessError::ParseSquare(string.to_owned())),
+        }
+    }
+}
+
+impl core::fmt::Display for Square {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}{}", File::from(*self).to_char(), Rank::from(*self).to_char())
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Ord, PartialOrd)]
+pub enum File {
+    A, B, C, D, E, F, G, H,
+}
+
+impl File {
+    pub const ALL: [File; 8] = [File::A, File::B, File::C, File::D, File::E, File::F, File::G, File::H];
+
+    ///Panics if `index` is 8 or more.
+    pub fn new(index: u8) -> File {
+        File::ALL[index as usize]
+    }
+
+    pub fn from_char(c: char) -> Option<File> {
+        match c.to_ascii_lowercase() {
+            c @ 'a'..='h' => Some(File::new(c as u8 - b'a')),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        (b'a' + self as u8) as char
+    }
+
+    pub fn to_bitboard(self) -> Bitboard {
+        Bitboard(0x0101010101010101 << self as u8)
+    }
+}
+
+impl From<Square> for File {
+    fn from(square: Square) -> File {
+        File::new(square.file())
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Ord, PartialOrd)]
+pub enum Rank {
+    First, Second, Third, Fourth, Fifth, Sixth, Seventh, Eighth,
+}
+
+impl Rank {
+    pub const ALL: [Rank; 8] = [Rank::First, Rank::Second, Rank::Third, Rank::Fourth,
+                                Rank::Fifth, Rank::Sixth, Rank::Seventh, Rank::Eighth];
 
-        if file < 'a' || file > 'h' {
-            return Err(ChessError::ParseSquare(string.to_owned())); } 
-        if rank <'1' || rank > '8' {
-            return Err(ChessError::ParseSquare(string.to_owned()));
+    ///Panics if `index` is 8 or more.
+    pub fn new(index: u8) -> Rank {
+        Rank::ALL[index as usize]
+    }
+
+    pub fn from_char(c: char) -> Option<Rank> {
+        match c {
+            '1'..='8' => Some(Rank::new(c as u8 - b'1')),
+            _ => None,
         }
+    }
 
-        Ok(Square::from((rank as u8 - '1' as u8) * 8 + (file as u8 - 'a' as u8) ))
+    pub fn to_char(self) -> char {
+        (b'1' + self as u8) as char
+    }
+
+    pub fn to_bitboard(self) -> Bitboard {
+        Bitboard(0xFF << (8 * self as u8))
+    }
+
+    ///The rank as seen from `color`'s side of the board, so the second rank
+    ///for black is the seventh.
+    pub fn relative(self, color: Color) -> Rank {
+        match color {
+            Color::White => self,
+            Color::Black => Rank::new(7 - self as u8),
+        }
     }
 }
 
-impl std::fmt::Display for Square {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}{}", (self.file() + 'a' as u8) as char, self.rank()+1)
+impl From<Square> for Rank {
+    fn from(square: Square) -> Rank {
+        Rank::new(square.rank())
     }
 }
 
@@ -90,12 +191,37 @@ pub enum Direction {
 }
 
 impl Direction {
+    ///Clockwise, starting from `Up`. The opposite of `ALL[i]` is
+    ///`ALL[(i + 4) % 8]`.
+    pub const ALL: [Direction; 8] = [
+        Direction::Up, Direction::UpRight, Direction::Right, Direction::DownRight,
+        Direction::Down, Direction::DownLeft, Direction::Left, Direction::UpLeft,
+    ];
+
     pub fn pawn(color: Color) -> Direction{
         match color {
             Color::White => Direction::Up,
             Color::Black => Direction::Down,
         }
     }
+
+    ///Position of the direction in `Direction::ALL`.
+    pub fn index(self) -> usize {
+        match self {
+            Direction::Up => 0,
+            Direction::UpRight => 1,
+            Direction::Right => 2,
+            Direction::DownRight => 3,
+            Direction::Down => 4,
+            Direction::DownLeft => 5,
+            Direction::Left => 6,
+            Direction::UpLeft => 7,
+        }
+    }
+
+    pub fn opposite(self) -> Direction {
+        Direction::ALL[(self.index() + 4) % 8]
+    }
 }
 
 impl Add<Direction> for Square {
@@ -129,7 +255,7 @@ impl Iterator for Ray {
 impl From<u8> for Square {
     fn from(t: u8) -> Square {
         unsafe {
-            std::mem::transmute(t)
+            core::mem::transmute(t)
         }
     }
 }
@@ -156,28 +282,27 @@ pub struct Bitboard(pub u64);
 
 impl Display for Bitboard {
     
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut s = String::new();
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         for rank in (0..8u8).rev() {
             for file in 0..8u8 {
                 match self.at(Square::from(rank*8 + file)) {
-                    true => s.push('O'),
-                    false => s.push('.'),
+                    true => write!(f, "O")?,
+                    false => write!(f, ".")?,
                 }
             }
-            s.push('\n');
+            writeln!(f)?;
         }
-        write!(f,"{}", s)
+        Ok(())
     }
 }
 
 impl Bitboard {
 
-    pub fn empty() -> Bitboard {
+    pub const fn empty() -> Bitboard {
         Bitboard(0)
     }
 
-    pub fn full() -> Bitboard {
+    pub const fn full() -> Bitboard {
         Bitboard(0xFFFFFFFFFFFFFFFF)
     }
 
@@ -189,6 +314,56 @@ impl Bitboard {
         self.0.count_ones()
     }
 
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn more_than_one(self) -> bool {
+        self.0 & self.0.wrapping_sub(1) != 0
+    }
+
+    ///The lowest square, a1 first.
+    pub fn lsb(self) -> Option<Square> {
+        match self.0 {
+            0 => None,
+            bits => Some(Square::from(bits.trailing_zeros() as u8)),
+        }
+    }
+
+    ///The highest square, h8 first.
+    pub fn msb(self) -> Option<Square> {
+        match self.0 {
+            0 => None,
+            bits => Some(Square::from(63 - bits.leading_zeros() as u8)),
+        }
+    }
+
+    ///Removes and returns the lowest square.
+    pub fn pop_lsb(&mut self) -> Option<Square> {
+        let square = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
+    ///Moves every square one step in `direction`. Squares that would leave
+    ///the board, including around the a and h files, are dropped.
+    pub fn shift(self, direction: Direction) -> Bitboard {
+        const NOT_A_FILE: u64 = !0x0101010101010101;
+        const NOT_H_FILE: u64 = !0x8080808080808080;
+
+        let bits = self.0;
+        Bitboard(match direction {
+            Direction::Up => bits << 8,
+            Direction::Down => bits >> 8,
+            Direction::Right => (bits & NOT_H_FILE) << 1,
+            Direction::Left => (bits & NOT_A_FILE) >> 1,
+            Direction::UpRight => (bits & NOT_H_FILE) << 9,
+            Direction::UpLeft => (bits & NOT_A_FILE) << 7,
+            Direction::DownRight => (bits & NOT_H_FILE) >> 7,
+            Direction::DownLeft => (bits & NOT_A_FILE) >> 9,
+        })
+    }
+
     pub fn flip_diagonal(self) -> Bitboard {
         let k1: u64 = 0x5500550055005500;
         let k2: u64 = 0x3333000033330000;
@@ -205,15 +380,33 @@ impl Bitboard {
         Bitboard(x)
     }
 
+    ///Mirrors the bitboard across the horizontal axis, rank 1 becomes rank 8.
+    pub fn flip_vertical(self) -> Bitboard {
+        Bitboard(self.0.swap_bytes())
+    }
+
+    ///Mirrors the bitboard across the vertical axis, file a becomes file h.
+    pub fn flip_horizontal(self) -> Bitboard {
+        let k1: u64 = 0x5555555555555555;
+        let k2: u64 = 0x3333333333333333;
+        let k4: u64 = 0x0f0f0f0f0f0f0f0f;
+
+        let mut x = self.0;
+        x = ((x >> 1) & k1) | ((x & k1) << 1);
+        x = ((x >> 2) & k2) | ((x & k2) << 2);
+        x = ((x >> 4) & k4) | ((x & k4) << 4);
+        Bitboard(x)
+    }
+
     pub fn at(self, square: Square) -> bool {
         (self & square.to_bitboard()).0 != 0
     }
 
     pub fn set(&mut self, square: Square) {
-        self.0 = self.0 | square.to_bitboard().0
+        self.0 |= square.to_bitboard().0
     }
     pub fn unset(&mut self, square: Square) {
-        self.0 = self.0 & !square.to_bitboard().0
+        self.0 &= !square.to_bitboard().0
     }
 
 }
@@ -234,6 +427,69 @@ impl BitAnd<Bitboard> for Bitboard {
     }
 }
 
+impl BitXor<Bitboard> for Bitboard {
+    type Output = Bitboard;
+
+    fn bitxor(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(self.0 ^ rhs.0)
+    }
+}
+
+///The squares of `self` that aren't in `rhs`.
+impl Sub<Bitboard> for Bitboard {
+    type Output = Bitboard;
+
+    fn sub(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(self.0 & !rhs.0)
+    }
+}
+
+///Same as [`Bitboard::shift`].
+impl Shl<Direction> for Bitboard {
+    type Output = Bitboard;
+
+    fn shl(self, direction: Direction) -> Bitboard {
+        self.shift(direction)
+    }
+}
+
+impl BitOrAssign<Bitboard> for Bitboard {
+    fn bitor_assign(&mut self, rhs: Bitboard) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAndAssign<Bitboard> for Bitboard {
+    fn bitand_assign(&mut self, rhs: Bitboard) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl BitXorAssign<Bitboard> for Bitboard {
+    fn bitxor_assign(&mut self, rhs: Bitboard) {
+        self.0 ^= rhs.0;
+    }
+}
+
+impl FromIterator<Square> for Bitboard {
+    fn from_iter<I: IntoIterator<Item = Square>>(squares: I) -> Bitboard {
+        let mut bitboard = Bitboard::empty();
+        for square in squares {
+            bitboard.set(square);
+        }
+        bitboard
+    }
+}
+
+impl IntoIterator for Bitboard {
+    type Item = Square;
+    type IntoIter = Squares;
+
+    fn into_iter(self) -> Squares {
+        self.squares()
+    }
+}
+
 impl Not for Bitboard {
     type Output = Bitboard;
     fn not(self) -> Bitboard {
@@ -256,3 +512,44 @@ impl DerefMut for Bitboard {
     }
 
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifts_drop_wrapping_squares() {
+        let edges = Square::A4.to_bitboard() | Square::H4.to_bitboard();
+        assert_eq!(edges.shift(Direction::Right), Square::B4.to_bitboard());
+        assert_eq!(edges.shift(Direction::Left), Square::G4.to_bitboard());
+        assert_eq!(edges << Direction::UpLeft, Square::G5.to_bitboard());
+        assert_eq!(Rank::Eighth.to_bitboard().shift(Direction::Up), Bitboard::empty());
+    }
+
+    #[test]
+    fn scanning() {
+        let mut bitboard: Bitboard = [Square::C2, Square::F7, Square::A5].iter().copied().collect();
+        assert_eq!(bitboard.lsb(), Some(Square::C2));
+        assert_eq!(bitboard.msb(), Some(Square::F7));
+        assert!(bitboard.more_than_one());
+        assert_eq!(bitboard.pop_lsb(), Some(Square::C2));
+        assert_eq!(bitboard.pop_lsb(), Some(Square::A5));
+        assert!(!bitboard.more_than_one());
+        assert_eq!(bitboard.into_iter().collect::<Vec<_>>(), [Square::F7]);
+        assert_eq!(bitboard.pop_lsb(), Some(Square::F7));
+        assert!(bitboard.is_empty());
+        assert_eq!(bitboard.pop_lsb(), None);
+    }
+
+    #[test]
+    fn files_and_ranks() {
+        assert_eq!("e4".parse::<Square>().unwrap(), Square::new(File::E, Rank::Fourth));
+        assert!("i1".parse::<Square>().is_err());
+        assert!("a9".parse::<Square>().is_err());
+        assert!("a1a".parse::<Square>().is_err());
+        assert_eq!(File::from(Square::G3), File::G);
+        assert_eq!(Rank::Second.relative(Color::Black), Rank::Seventh);
+        assert_eq!(Square::H8.to_string(), "h8");
+    }
+}
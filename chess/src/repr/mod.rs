@@ -1,10 +1,12 @@
 pub mod bitboard;
 pub mod pieces;
+pub mod move_list;
 
 
 pub use pieces::{Pieces, Piece};
-pub use bitboard::{Bitboard, Square, Direction};
-use std::ops::{Index, IndexMut};
+pub use bitboard::{Bitboard, Square, Direction, File, Rank};
+pub use move_list::MoveList;
+use core::ops::{Index, IndexMut};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Move {
@@ -15,8 +17,55 @@ pub enum Move {
     QueensideCastle
 }
 
-impl std::fmt::Display for Move {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Move {
+    ///The square the piece moves from, the king's square for castling.
+    ///`color` is the side making the move, only castling needs it.
+    pub fn from(self, color: Color) -> Square {
+        match self {
+            Move::Normal(src, _, _) | Move::EnPassant(src, _) | Move::Promotion(src, _, _) => src,
+            Move::KingsideCastle | Move::QueensideCastle => self.castling_squares(color).0,
+        }
+    }
+
+    ///The square the piece moves to, where the king lands for castling.
+    pub fn to(self, color: Color) -> Square {
+        match self {
+            Move::Normal(_, dst, _) | Move::EnPassant(_, dst) | Move::Promotion(_, dst, _) => dst,
+            Move::KingsideCastle | Move::QueensideCastle => self.castling_squares(color).1,
+        }
+    }
+
+    pub fn is_promotion(self) -> bool {
+        matches!(self, Move::Promotion(..))
+    }
+
+    pub fn is_castle(self) -> bool {
+        matches!(self, Move::KingsideCastle | Move::QueensideCastle)
+    }
+
+    ///The piece a pawn promotes to.
+    pub fn promotion(self) -> Option<Piece> {
+        match self {
+            Move::Promotion(_, _, piece) => Some(piece),
+            _ => None,
+        }
+    }
+
+    ///King from, king to, rook from, rook to. Panics if this isn't a castling
+    ///move.
+    pub fn castling_squares(self, color: Color) -> (Square, Square, Square, Square) {
+        match (self, color) {
+            (Move::KingsideCastle, Color::White) => (Square::E1, Square::G1, Square::H1, Square::F1),
+            (Move::KingsideCastle, Color::Black) => (Square::E8, Square::G8, Square::H8, Square::F8),
+            (Move::QueensideCastle, Color::White) => (Square::E1, Square::C1, Square::A1, Square::D1),
+            (Move::QueensideCastle, Color::Black) => (Square::E8, Square::C8, Square::A8, Square::D8),
+            _ => panic!("{} isn't a castling move", self),
+        }
+    }
+}
+
+impl core::fmt::Display for Move {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Move::Normal(src, dst, _) => write!(f, "{}{}", src,dst),
             Move::EnPassant(src, dst) => write!(f, "{}{}", src,dst),
@@ -2,12 +2,10 @@ use crate::repr::{
     Color,
     bitboard::{Bitboard, Square}, 
 };
-use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
-use std::ops::{Index,IndexMut};
+use core::ops::{Index,IndexMut};
 
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Piece {
     Pawn,
     Knight,
@@ -18,6 +16,8 @@ pub enum Piece {
 }
 
 impl Piece {
+    pub const ALL: [Piece; 6] = [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King];
+
     pub fn to_ascii(self) -> char {
 
         match self {
@@ -72,7 +72,7 @@ impl Pieces {
     }
 
     pub fn at(&self, square: Square) -> Option<Piece> {
-        for piece in Piece::iter() {
+        for &piece in Piece::ALL.iter() {
             if self[piece].at(square) {
                 return Some(piece);
             }
@@ -81,7 +81,7 @@ impl Pieces {
     }
 
     pub fn unset(&mut self, square: Square) {
-        for piece in Piece::iter() {
+        for &piece in Piece::ALL.iter() {
             self[piece].unset(square);
         }
     }
@@ -94,6 +94,14 @@ impl Pieces {
     pub fn unoccupied(&self) -> Bitboard {
         Bitboard(!self.occupied().0)
     }
+
+    pub fn map(&self, f: impl Fn(Bitboard) -> Bitboard) -> Pieces {
+        let mut pieces = *self;
+        for bitboard in pieces.pieces.iter_mut() {
+            *bitboard = f(*bitboard);
+        }
+        pieces
+    }
 }
 
 
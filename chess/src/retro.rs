@@ -0,0 +1,234 @@
+//!Retrograde move generation: the moves that could have led to a position
+//!and the positions they were played from.
+//!
+//!A position doesn't record everything about its past, so predecessors are
+//!filled in as conservatively as possible. Castling rights are the current
+//!ones plus whatever an uncastling needs, there is no en passant square
+//!unless an en passant capture is taken back, and the halfmove clock is a
+//!guess.
+use alloc::vec::Vec;
+use core::iter::once;
+
+use crate::builder::PositionBuilder;
+use crate::constants::*;
+use crate::position::Position;
+use crate::repr::*;
+
+///A move taken back.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Unmove {
+    ///The move as it was played in the previous position.
+    pub mv: Move,
+    ///The piece it captured, which the unmove puts back. A pawn for en
+    ///passant.
+    pub captured: Option<Piece>,
+}
+
+impl Position {
+    ///Every move that could have led here, with the legal position it was
+    ///played from. Each previous position `p` satisfies
+    ///`p.make_move(unmove.mv) == *self`, except that an en passant square
+    ///nobody can capture on may be missing from `self`.
+    pub fn legal_unmoves(&self) -> Vec<(Unmove, Position)> {
+        let mover = self.turn.other();
+        let backward = Direction::pawn(mover).opposite();
+        let occupied = self.board().occupied();
+        let empty = !occupied;
+        let last_rank = Rank::Eighth.relative(mover).to_bitboard();
+
+        let mut base = PositionBuilder::from(*self);
+        base.turn(mover).en_passant(None).half_moves(self.half_moves.saturating_sub(1));
+        if mover == Color::Black {
+            base.full_moves(self.full_moves.saturating_sub(1).max(1));
+        }
+
+        let mut unmoves = Vec::new();
+        let mut keep = |mv: Move, captured: Option<Piece>, previous: &PositionBuilder| {
+            if let Ok(previous) = previous.build() {
+                if previous.legal_move_list().contains(&mv) && previous.make_move(mv).same_as(self) {
+                    unmoves.push((Unmove { mv, captured }, previous));
+                }
+            }
+        };
+        // The piece on `to` goes back to `from` as `before`, and `captured`
+        // reappears where it was taken.
+        let retract = |from: Square, to: Square, before: Piece, captured: Option<Piece>| {
+            let mut previous = base;
+            previous.remove(to).put(from, mover, before);
+            if let Some(piece) = captured {
+                previous.put(to, self.turn, piece);
+            }
+            previous
+        };
+
+        for to in self.board()[mover].occupied() {
+            let piece = match self.at(to) {
+                Some((_, piece)) => piece,
+                None => continue,
+            };
+            let behind = to.to_bitboard().shift(backward);
+            let diagonally_behind = behind.shift(Direction::Left) | behind.shift(Direction::Right);
+            let uncaptures = uncaptures(to);
+
+            if piece == Piece::Pawn {
+                for from in behind & empty {
+                    keep(Move::Normal(from, to, piece), None, &retract(from, to, piece, None));
+
+                    let double = from.to_bitboard().shift(backward) & empty & Rank::Second.relative(mover).to_bitboard();
+                    for from in double {
+                        keep(Move::Normal(from, to, piece), None, &retract(from, to, piece, None));
+                    }
+                }
+
+                for from in diagonally_behind & empty {
+                    for &captured in uncaptures.iter().flatten() {
+                        keep(Move::Normal(from, to, piece), Some(captured), &retract(from, to, piece, Some(captured)));
+                    }
+
+                    let captured_pawn = to + backward;
+                    if Rank::from(to) == Rank::Sixth.relative(mover) && empty.at(captured_pawn) {
+                        let mut previous = retract(from, to, piece, None);
+                        previous.put(captured_pawn, self.turn, Piece::Pawn).en_passant(Some(to));
+                        keep(Move::EnPassant(from, to), Some(Piece::Pawn), &previous);
+                    }
+                }
+                continue;
+            }
+
+            for from in reach(piece, to, occupied) & empty {
+                for captured in once(None).chain(uncaptures.iter().flatten().copied().map(Some)) {
+                    keep(Move::Normal(from, to, piece), captured, &retract(from, to, piece, captured));
+                }
+            }
+
+            if piece != Piece::King && last_rank.at(to) {
+                for from in behind & empty {
+                    keep(Move::Promotion(from, to, piece), None, &retract(from, to, Piece::Pawn, None));
+                }
+                for from in diagonally_behind & empty {
+                    for &captured in uncaptures.iter().flatten() {
+                        keep(Move::Promotion(from, to, piece), Some(captured),
+                             &retract(from, to, Piece::Pawn, Some(captured)));
+                    }
+                }
+            }
+
+            if piece == Piece::King {
+                for &castle in [Move::KingsideCastle, Move::QueensideCastle].iter() {
+                    let (king_from, king_to, rook_from, rook_to) = castle.castling_squares(mover);
+                    if to != king_to || self.at(rook_to) != Some((mover, Piece::Rook)) {
+                        continue;
+                    }
+                    let mut rights = self.castling_rights[mover];
+                    match castle {
+                        Move::KingsideCastle => rights.kingside = true,
+                        _ => rights.queenside = true,
+                    }
+                    let mut previous = base;
+                    previous.remove(king_to).remove(rook_to)
+                            .put(king_from, mover, Piece::King)
+                            .put(rook_from, mover, Piece::Rook)
+                            .castling_rights(mover, rights);
+                    keep(castle, None, &previous);
+                }
+            }
+        }
+
+        unmoves
+    }
+}
+
+///Where a piece on `square` can have come from, ignoring captures.
+fn reach(piece: Piece, square: Square, occupied: Bitboard) -> Bitboard {
+    let rook = || horizontal_slide(square, occupied).0 | vertical_slide(square, occupied).0;
+    let bishop = || main_diagonal_slide(square, occupied).0 | second_diagonal_slide(square, occupied).0;
+    match piece {
+        Piece::Knight => KNIGHT_ATTACKS[square as usize],
+        Piece::King => KING_ATTACKS[square as usize],
+        Piece::Rook => rook(),
+        Piece::Bishop => bishop(),
+        Piece::Queen => rook() | bishop(),
+        Piece::Pawn => Bitboard::empty(),
+    }
+}
+
+///What could have been captured on `square`: any piece but a king, and no
+///pawn on the first or last rank.
+fn uncaptures(square: Square) -> [Option<Piece>; 5] {
+    let back_rank = matches!(Rank::from(square), Rank::First | Rank::Eighth);
+    [
+        if back_rank { None } else { Some(Piece::Pawn) },
+        Some(Piece::Knight),
+        Some(Piece::Bishop),
+        Some(Piece::Rook),
+        Some(Piece::Queen),
+    ]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn has_unmove(position: &Position, mv: Move, captured: Option<Piece>) -> bool {
+        position.legal_unmoves().iter().any(|(unmove, _)| *unmove == Unmove { mv, captured })
+    }
+
+    #[test]
+    fn every_move_can_be_taken_back() {
+        let position = Position::from_fen(KIWIPETE).unwrap();
+        for (mv, child) in position.children() {
+            let unmoves = child.legal_unmoves();
+            assert!(unmoves.iter().any(|(unmove, previous)| unmove.mv == mv && previous.board() == position.board()),
+                    "{} can't be taken back", mv);
+            for (unmove, previous) in unmoves {
+                assert!(previous.make_move(unmove.mv).same_as(&child));
+            }
+        }
+    }
+
+    #[test]
+    fn special_unmoves() {
+        let position = Position::from_fen("r4rk1/8/8/8/8/8/8/2KR4 b - - 0 1").unwrap();
+        assert!(has_unmove(&position, Move::QueensideCastle, None));
+        assert!(has_unmove(&position, Move::Normal(Square::E1, Square::D1, Piece::Rook), Some(Piece::Knight)));
+        assert!(!has_unmove(&position, Move::Normal(Square::E1, Square::D1, Piece::Rook), Some(Piece::Pawn)));
+
+        let position = Position::from_fen("4k3/8/3P4/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(has_unmove(&position, Move::EnPassant(Square::E5, Square::D6), Some(Piece::Pawn)));
+        assert!(has_unmove(&position, Move::Normal(Square::C5, Square::D6, Piece::Pawn), Some(Piece::Queen)));
+        assert!(!has_unmove(&position, Move::Normal(Square::C5, Square::D6, Piece::Pawn), None));
+
+        let position = Position::from_fen("1N2k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(has_unmove(&position, Move::Promotion(Square::B7, Square::B8, Piece::Knight), None));
+        assert!(has_unmove(&position, Move::Promotion(Square::A7, Square::B8, Piece::Knight), Some(Piece::Rook)));
+
+        // After a double push that can be taken en passant only that push
+        // can be taken back.
+        let push = Move::Normal(Square::E2, Square::E4, Piece::Pawn);
+        let position = Position::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let unmoves = position.legal_unmoves();
+        assert_eq!(unmoves.len(), 1);
+        assert_eq!(unmoves[0].0.mv, push);
+        let position = Position::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").unwrap();
+        assert!(!has_unmove(&position, push, None));
+
+        // An en passant square nobody can capture on makes no difference.
+        let position = Position::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert!(has_unmove(&position, push, None));
+        let with_square = Position::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+        assert_eq!(with_square.legal_unmoves(), position.legal_unmoves());
+    }
+
+    #[test]
+    fn no_duplicate_unmoves() {
+        for fen in ["4k3/8/8/8/4P3/8/8/4K3 b - - 0 1", "r4rk1/8/8/8/8/8/8/2KR4 b - - 0 1", KIWIPETE].iter() {
+            let unmoves: Vec<Unmove> = Position::from_fen(fen).unwrap().legal_unmoves().into_iter().map(|(unmove, _)| unmove).collect();
+            for (index, unmove) in unmoves.iter().enumerate() {
+                assert!(!unmoves[..index].contains(unmove), "{:?} comes back twice in {}", unmove, fen);
+            }
+        }
+    }
+}
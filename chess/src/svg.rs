@@ -0,0 +1,379 @@
+//!Renders positions to self-contained SVG diagrams. The pieces are drawn
+//!with paths embedded as `<symbol>` definitions, so the output needs no
+//!image files or fonts and can be dropped straight into HTML, markdown
+//!reports or docs.
+use std::fmt::Write;
+
+use crate::position::Position;
+use crate::repr::*;
+
+const SQUARE_SIZE: f64 = 45.0;
+const MARGIN: f64 = 20.0;
+
+const LIGHT_SQUARE: &str = "#f0d9b5";
+const DARK_SQUARE: &str = "#b58863";
+const LAST_MOVE: &str = "#cdd16a";
+const CHECK: &str = "#ff0000";
+const ARROW: &str = "#15781b";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arrow {
+    pub from: Square,
+    pub to: Square,
+    pub color: String,
+}
+
+impl Arrow {
+    pub fn new(from: Square, to: Square) -> Arrow {
+        Arrow { from, to, color: ARROW.to_owned() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Highlight {
+    pub square: Square,
+    pub color: String,
+}
+
+impl Highlight {
+    pub fn new(square: Square, color: &str) -> Highlight {
+        Highlight { square, color: color.to_owned() }
+    }
+}
+
+///Everything that can be drawn on top of the bare position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    ///Width and height of the whole image in pixels.
+    pub size: u32,
+    ///Draw the board from black's point of view.
+    pub flipped: bool,
+    pub coordinates: bool,
+    pub arrows: Vec<Arrow>,
+    pub highlights: Vec<Highlight>,
+    ///Source and destination squares of the last move, shaded.
+    pub last_move: Option<(Square, Square)>,
+    ///Mark the king of the side to move if it is in check.
+    pub check: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            size: 400,
+            flipped: false,
+            coordinates: true,
+            arrows: Vec::new(),
+            highlights: Vec::new(),
+            last_move: None,
+            check: true,
+        }
+    }
+}
+
+fn piece_id(color: Color, piece: Piece) -> String {
+    let color = match color {
+        Color::White => "white",
+        Color::Black => "black",
+    };
+    let piece = match piece {
+        Piece::Pawn => "pawn",
+        Piece::Knight => "knight",
+        Piece::Bishop => "bishop",
+        Piece::Rook => "rook",
+        Piece::Queen => "queen",
+        Piece::King => "king",
+    };
+    format!("{}-{}", color, piece)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+struct Layout {
+    flipped: bool,
+    margin: f64,
+}
+
+impl Layout {
+    ///Top left corner of a square.
+    fn corner(&self, square: Square) -> (f64, f64) {
+        let (file, rank) = match self.flipped {
+            false => (square.file(), 7 - square.rank()),
+            true => (7 - square.file(), square.rank()),
+        };
+        (self.margin + file as f64 * SQUARE_SIZE, self.margin + rank as f64 * SQUARE_SIZE)
+    }
+
+    fn center(&self, square: Square) -> (f64, f64) {
+        let (x, y) = self.corner(square);
+        (x + SQUARE_SIZE / 2.0, y + SQUARE_SIZE / 2.0)
+    }
+}
+
+///Every piece stands on this, in a 45 by 45 box.
+const PIECE_BASE: &str = "<path d=\"M11 39H34V35H11Z\"/>";
+
+///The body of `piece` above `PIECE_BASE`. Details drawn in `currentColor`
+///stand out against it.
+fn piece_shape(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => concat!(
+            "<path d=\"M16 35C16 28 19 24 20.5 19.5H24.5C26 24 29 28 29 35Z\"/>",
+            "<circle cx=\"22.5\" cy=\"14\" r=\"5.5\"/>",
+        ),
+        Piece::Knight => concat!(
+            "<path d=\"M33 35C33 25 31 14 24 10L23 6L20 9.5L17 7V12C13 15 10 21 10 25",
+            "C10 27.5 12.5 28.5 14 27C16 25 18.5 24.5 21 23C19 27 15 30 15 35Z\"/>",
+            "<circle cx=\"18.5\" cy=\"14.5\" r=\"1.3\" fill=\"currentColor\" stroke=\"none\"/>",
+        ),
+        Piece::Bishop => concat!(
+            "<path d=\"M15 35C15 27 17 21 22.5 12C28 21 30 27 30 35Z\"/>",
+            "<circle cx=\"22.5\" cy=\"9.5\" r=\"2.5\"/>",
+            "<path d=\"M25 19L21 25\" stroke=\"currentColor\" stroke-width=\"1.8\" fill=\"none\"/>",
+        ),
+        Piece::Rook => concat!(
+            "<path d=\"M14 35L15 17H30L31 35Z\"/>",
+            "<path d=\"M12 17V9H16V12H20.5V9H24.5V12H29V9H33V17Z\"/>",
+        ),
+        Piece::Queen => concat!(
+            "<path d=\"M13 35L10 15L15.5 26L16 12L19.5 24L22.5 10L25.5 24L29 12L29.5 26L35 15L32 35Z\"/>",
+            "<circle cx=\"10\" cy=\"14\" r=\"2\"/><circle cx=\"16\" cy=\"11\" r=\"2\"/>",
+            "<circle cx=\"22.5\" cy=\"9\" r=\"2\"/><circle cx=\"29\" cy=\"11\" r=\"2\"/>",
+            "<circle cx=\"35\" cy=\"14\" r=\"2\"/>",
+        ),
+        Piece::King => concat!(
+            "<path d=\"M13 35C11 27 12 21 17 20C20 19.5 21.5 22 22.5 24C23.5 22 25 19.5 28 20",
+            "C33 21 34 27 32 35Z\"/>",
+            "<path d=\"M21 5H24V9H27.5V12H24V18H21V12H17.5V9H21Z\"/>",
+        ),
+    }
+}
+
+fn write_defs(svg: &mut String) {
+    svg.push_str("<defs>");
+    for &color in [Color::White, Color::Black].iter() {
+        let (fill, stroke) = match color {
+            Color::White => ("#ffffff", "#000000"),
+            Color::Black => ("#000000", "#ffffff"),
+        };
+        for &piece in Piece::ALL.iter() {
+            let _ = write!(
+                svg,
+                "<symbol id=\"{}\" viewBox=\"0 0 45 45\"><g fill=\"{}\" stroke=\"{}\" color=\"{}\" \
+                 stroke-width=\"1.5\" stroke-linejoin=\"round\">{}{}</g></symbol>",
+                piece_id(color, piece),
+                fill,
+                stroke,
+                stroke,
+                piece_shape(piece),
+                PIECE_BASE,
+            );
+        }
+    }
+    let _ = write!(
+        svg,
+        "<radialGradient id=\"check-gradient\" r=\"0.5\">\
+         <stop offset=\"0%\" stop-color=\"{0}\" stop-opacity=\"1\"/>\
+         <stop offset=\"50%\" stop-color=\"{0}\" stop-opacity=\"0.7\"/>\
+         <stop offset=\"100%\" stop-color=\"{0}\" stop-opacity=\"0\"/>\
+         </radialGradient>",
+        CHECK
+    );
+    svg.push_str("</defs>");
+}
+
+fn write_arrow(svg: &mut String, layout: &Layout, arrow: &Arrow) {
+    let color = escape(&arrow.color);
+    let (x1, y1) = layout.center(arrow.from);
+    let (x2, y2) = layout.center(arrow.to);
+
+    if arrow.from == arrow.to {
+        let _ = write!(
+            svg,
+            "<circle class=\"arrow\" cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"none\" \
+             stroke=\"{}\" stroke-width=\"{:.1}\" opacity=\"0.8\"/>",
+            x1, y1, SQUARE_SIZE * 0.45, color, SQUARE_SIZE * 0.08
+        );
+        return;
+    }
+
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let (ux, uy) = (dx / length, dy / length);
+
+    let head_length = SQUARE_SIZE * 0.4;
+    let head_width = SQUARE_SIZE * 0.25;
+    let (bx, by) = (x2 - ux * head_length, y2 - uy * head_length);
+
+    let _ = write!(
+        svg,
+        "<g class=\"arrow\" opacity=\"0.8\"><line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" \
+         stroke=\"{}\" stroke-width=\"{:.1}\" stroke-linecap=\"round\"/>\
+         <polygon points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"{}\"/></g>",
+        x1, y1, bx, by, color, SQUARE_SIZE * 0.2,
+        x2, y2,
+        bx - uy * head_width, by + ux * head_width,
+        bx + uy * head_width, by - ux * head_width,
+        color
+    );
+}
+
+///Renders `position` as a standalone SVG document.
+pub fn render(position: &Position, options: &SvgOptions) -> String {
+    let margin = if options.coordinates { MARGIN } else { 0.0 };
+    let layout = Layout { flipped: options.flipped, margin };
+    let full = 8.0 * SQUARE_SIZE + 2.0 * margin;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+         version=\"1.1\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {1} {1}\">",
+        options.size, full
+    );
+    write_defs(&mut svg);
+
+    if options.coordinates {
+        let _ = write!(svg, "<rect x=\"0\" y=\"0\" width=\"{0}\" height=\"{0}\" fill=\"#212121\"/>", full);
+    }
+
+    for square in Square::ALL.iter().copied() {
+        let (x, y) = layout.corner(square);
+        let fill = match (square.file() + square.rank()) % 2 {
+            0 => DARK_SQUARE,
+            _ => LIGHT_SQUARE,
+        };
+        let _ = write!(
+            svg,
+            "<rect class=\"square {}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            square, x, y, SQUARE_SIZE, SQUARE_SIZE, fill
+        );
+    }
+
+    if let Some((src, dst)) = options.last_move {
+        for square in [src, dst].iter().copied() {
+            let (x, y) = layout.corner(square);
+            let _ = write!(
+                svg,
+                "<rect class=\"lastmove\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"0.6\"/>",
+                x, y, SQUARE_SIZE, SQUARE_SIZE, LAST_MOVE
+            );
+        }
+    }
+
+    for highlight in options.highlights.iter() {
+        let (x, y) = layout.corner(highlight.square);
+        let _ = write!(
+            svg,
+            "<rect class=\"highlight\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"0.5\"/>",
+            x, y, SQUARE_SIZE, SQUARE_SIZE, escape(&highlight.color)
+        );
+    }
+
+    if options.check && position.in_check() {
+        if let Some(king) = position.board()[position.turn][Piece::King].squares().next() {
+            let (x, y) = layout.corner(king);
+            let _ = write!(
+                svg,
+                "<rect class=\"check\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"url(#check-gradient)\"/>",
+                x, y, SQUARE_SIZE, SQUARE_SIZE
+            );
+        }
+    }
+
+    if options.coordinates {
+        for i in 0..8u8 {
+            let file = if options.flipped { 7 - i } else { i };
+            let rank = if options.flipped { i } else { 7 - i };
+            let offset = margin + i as f64 * SQUARE_SIZE + SQUARE_SIZE / 2.0;
+            let _ = write!(
+                svg,
+                "<text class=\"coord\" x=\"{:.1}\" y=\"{:.1}\" font-size=\"14\" text-anchor=\"middle\" \
+                 dominant-baseline=\"central\" fill=\"#e5e5e5\">{}</text>",
+                offset, full - margin / 2.0, (b'a' + file) as char
+            );
+            let _ = write!(
+                svg,
+                "<text class=\"coord\" x=\"{:.1}\" y=\"{:.1}\" font-size=\"14\" text-anchor=\"middle\" \
+                 dominant-baseline=\"central\" fill=\"#e5e5e5\">{}</text>",
+                margin / 2.0, offset, rank + 1
+            );
+        }
+    }
+
+    for square in Square::ALL.iter().copied() {
+        if let Some((color, piece)) = position.at(square) {
+            let (x, y) = layout.corner(square);
+            let _ = write!(
+                svg,
+                "<use xlink:href=\"#{0}\" href=\"#{0}\" x=\"{1}\" y=\"{2}\" width=\"{3}\" height=\"{3}\"/>",
+                piece_id(color, piece), x, y, SQUARE_SIZE
+            );
+        }
+    }
+
+    for arrow in options.arrows.iter() {
+        write_arrow(&mut svg, &layout, arrow);
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+impl Position {
+    ///Shorthand for [`render`] with the default options.
+    pub fn to_svg(&self) -> String {
+        render(self, &SvgOptions::default())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starting_position() {
+        let svg = Position::starting().to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<use ").count(), 32);
+        assert_eq!(svg.matches("class=\"coord\"").count(), 16);
+        assert!(!svg.contains("class=\"check\""));
+
+        // The pieces are shapes, not glyphs from whatever fonts are around.
+        let defs = &svg[svg.find("<defs>").unwrap()..svg.find("</defs>").unwrap()];
+        assert_eq!(defs.matches("<symbol ").count(), 12);
+        assert!(!defs.contains("<text") && !svg.contains("font-family"));
+    }
+
+    #[test]
+    fn flipped_board() {
+        let options = SvgOptions { flipped: true, coordinates: false, ..SvgOptions::default() };
+        let svg = render(&Position::starting(), &options);
+        assert!(svg.contains("<rect class=\"square h1\" x=\"0\" y=\"0\""));
+        assert!(svg.contains("<rect class=\"square a8\" x=\"315\" y=\"315\""));
+    }
+
+    #[test]
+    fn overlays() {
+        let position = Position::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        let options = SvgOptions {
+            arrows: vec![Arrow::new(Square::D8, Square::H4)],
+            highlights: vec![Highlight::new(Square::E1, "\"red\"")],
+            last_move: Some((Square::D8, Square::H4)),
+            ..SvgOptions::default()
+        };
+        let svg = render(&position, &options);
+        assert_eq!(svg.matches("class=\"lastmove\"").count(), 2);
+        assert_eq!(svg.matches("class=\"arrow\"").count(), 1);
+        assert!(svg.contains("class=\"check\""));
+        assert!(svg.contains("&quot;red&quot;"));
+    }
+}
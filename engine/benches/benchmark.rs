@@ -1,7 +1,15 @@
-
 use criterion::{criterion_group, criterion_main, Criterion};
 use engine::*;
+use engine::time::Limits;
+use engine::tt::TranspositionTable;
 use chess::Chessboard;
+use std::time::Duration;
+
+const POSITIONS: [(&str, &str); 3] = [
+    ("starting", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+    ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+];
 
 pub fn negamax_starting_bench(c: &mut Criterion) {
     let chessboard = Chessboard::starting();
@@ -13,5 +21,28 @@ pub fn negamax_starting_bench(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, negamax_starting_bench);
+///Searches each position to a fixed depth with and without `MovePicker`,
+///printing how many nodes each needs before timing them.
+pub fn move_ordering_bench(c: &mut Criterion) {
+    const DEPTH: u32 = 4;
+    let mut group = c.benchmark_group("move ordering");
+    group.sample_size(10);
+
+    for &(name, fen) in POSITIONS.iter() {
+        let chessboard = Chessboard::from_fen(fen).unwrap();
+        for &ordered in [true, false].iter() {
+            let options = SearchOptions { move_ordering: ordered, move_overhead: Duration::ZERO, ..SearchOptions::default() };
+            let run = || {
+                let tt = TranspositionTable::new(16);
+                search(&chessboard, &Limits::depth(DEPTH), &tt, &options, &SearchControl::default(), &|_| {})
+            };
+            let label = format!("{} {}", name, if ordered { "ordered" } else { "unordered" });
+            println!("{}: {} nodes at depth {}", label, run().stats.nodes, DEPTH);
+            group.bench_function(&label, |b| b.iter(run));
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, negamax_starting_bench, move_ordering_bench);
 criterion_main!(benches);
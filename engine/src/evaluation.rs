@@ -4,6 +4,15 @@ use chess::*;
 pub const MIN_SCORE: f64 = -10000.0;
 pub const MAX_SCORE: f64 = 10000.0;
 
+///Centipawns from the side to move's point of view, the unit of the search.
+pub type Score = i32;
+///Larger than any score the search can return.
+pub const INFINITY: Score = 32000;
+///Being mated now. Mate in `n` plies is `MATE - n`.
+pub const MATE: Score = 31000;
+///Scores at least this far from 0 are mates.
+pub const MATE_BOUND: Score = MATE - 1000;
+
 
 const POSITION_KING_EARLY : [f64;64] = [
     0.05,0.05,0.50,0.05,0.05,0.05,0.50,0.05,
@@ -49,6 +58,48 @@ const POSITION_PAWN : Colored<[f64;64]> = Colored([
 
 
 
+///The weights of the evaluation, in centipawns so they can be UCI spin
+///options.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+    pub pawn: Score,
+    pub knight: Score,
+    pub bishop: Score,
+    pub rook: Score,
+    pub queen: Score,
+    ///The piece-square tables are scaled by this, in percent.
+    pub piece_square_percent: Score,
+    ///For every square a side attacks.
+    pub mobility: Score,
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams {
+            pawn: piece_value(Piece::Pawn),
+            knight: piece_value(Piece::Knight),
+            bishop: piece_value(Piece::Bishop),
+            rook: piece_value(Piece::Rook),
+            queen: piece_value(Piece::Queen),
+            piece_square_percent: 100,
+            mobility: 3,
+        }
+    }
+}
+
+///Material in centipawns, as `evaluate_color` counts it with the default
+///`EvalParams`. Kings have none.
+pub fn piece_value(piece: Piece) -> Score {
+    match piece {
+        Piece::Pawn => 100,
+        Piece::Knight | Piece::Bishop => 300,
+        Piece::Rook => 500,
+        Piece::Queen => 900,
+        Piece::King => 0,
+    }
+}
+
+
 pub fn game_over(chessboard: &Chessboard) -> Option<f64> {
     if let Some(result) = chessboard.game_result() {
         match result {
@@ -64,36 +115,38 @@ pub fn game_over(chessboard: &Chessboard) -> Option<f64> {
 }
 
 
-pub fn evaluate_color(chessboard: &Chessboard, color: Color) -> f64 {
+pub fn evaluate_color(chessboard: &Chessboard, color: Color, params: &EvalParams) -> f64 {
     let mut score = 0.0;
-    let board = chessboard.position.board;
+    let board = *chessboard.position.board();
+    let pawns = |centipawns: Score| centipawns as f64 / 100.0;
+    let piece_square = pawns(params.piece_square_percent);
 
 
     for sq in board[color][Piece::Pawn].squares() {
-        score += 1.0 + POSITION_PAWN[color][sq as usize];
+        score += pawns(params.pawn) + piece_square * POSITION_PAWN[color][sq as usize];
     }
     for sq in board[color][Piece::Knight].squares() {
-        score += 3.0 + POSITION_KNIGHT[sq as usize];
+        score += pawns(params.knight) + piece_square * POSITION_KNIGHT[sq as usize];
     }
 
-    score += 3.0 * board[color][Piece::Bishop].count() as f64;
-    score += 5.0 * board[color][Piece::Rook].count() as f64;
-    score += 9.0 * board[color][Piece::Queen].count() as f64;
+    score += pawns(params.bishop) * board[color][Piece::Bishop].count() as f64;
+    score += pawns(params.rook) * board[color][Piece::Rook].count() as f64;
+    score += pawns(params.queen) * board[color][Piece::Queen].count() as f64;
     
     if score > 10.0 {
         let king_sq = board[color][Piece::King].squares().next().unwrap();
-        score += POSITION_KING_EARLY[king_sq as usize]
+        score += piece_square * POSITION_KING_EARLY[king_sq as usize]
     }
 
     let attacked = board.attacks(color);
-    score += 0.03 * attacked.count() as f64; 
+    score += pawns(params.mobility) * attacked.count() as f64; 
     
     score
 }
 
 
 
-pub fn evaluate(chessboard: &Chessboard) -> f64 { 
+pub fn evaluate(chessboard: &Chessboard, params: &EvalParams) -> f64 { 
     
      // if let Some(score) = game_over(chessboard) {
      //    return score;
@@ -102,7 +155,48 @@ pub fn evaluate(chessboard: &Chessboard) -> f64 {
      let player = chessboard.position.turn;
      let opponent = player.other();
     
-     let score = evaluate_color(chessboard,player) - evaluate_color(chessboard,opponent);
+     let score = evaluate_color(chessboard,player,params) - evaluate_color(chessboard,opponent,params);
 
      score
 }
+
+
+///`evaluate` in centipawns, kept clear of the mate scores.
+pub fn evaluate_cp(chessboard: &Chessboard, params: &EvalParams) -> Score {
+    let score = (evaluate(chessboard, params) * 100.0).round() as Score;
+    score.clamp(1 - MATE_BOUND, MATE_BOUND - 1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::position::Position;
+
+    #[test]
+    fn evaluation_is_symmetric() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        let params = EvalParams::default();
+        for fen in fens.iter() {
+            let position = Position::from_fen(fen).unwrap();
+            let flipped = position.flip_colors();
+            assert_eq!(evaluate(&Chessboard::new(position), &params), evaluate(&Chessboard::new(flipped), &params));
+        }
+    }
+
+    #[test]
+    fn weights_change_the_evaluation() {
+        // A knight against two pawns.
+        let chessboard = Chessboard::from_fen("4k3/3pp3/8/8/8/8/8/1N2K3 w - - 0 1").unwrap();
+        let flat = EvalParams { piece_square_percent: 0, mobility: 0, ..EvalParams::default() };
+        assert_eq!(evaluate_cp(&chessboard, &flat), 100);
+        assert_eq!(evaluate_cp(&chessboard, &EvalParams { knight: 250, ..flat }), 50);
+        assert_eq!(evaluate_cp(&chessboard, &EvalParams { pawn: 80, ..flat }), 140);
+        assert_ne!(evaluate_cp(&chessboard, &EvalParams { mobility: 10, ..flat }), 100);
+    }
+}
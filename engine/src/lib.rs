@@ -1,121 +1,1115 @@
 pub mod uci;
 pub mod evaluation;
+pub mod time;
+pub mod tt;
+pub mod see;
+pub mod picker;
+pub mod options;
 
 use evaluation::*;
+use time::{Limits, TimeManager};
+use tt::{Bound, TranspositionTable, DEFAULT_HASH_MB};
 use chess::*;
-use chess::repr::Move;
-use rayon::prelude::*;
-use std::sync::{
-    Arc,
-    Mutex,
-};
+use chess::repr::{Color, Move, MoveList, Piece};
+use picker::{is_quiet, material_gain, mvv_lva, Heuristics, MovePicker};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
+///Deepest iteration when only the clock limits the search.
+pub const MAX_DEPTH: u32 = 64;
+///Search threads recurse up to `MAX_PLY` deep.
+const THREAD_STACK_SIZE: usize = 16 * 1024 * 1024;
+///Deepest ply the search goes to, quiescence included.
+pub const MAX_PLY: u32 = 128;
+///The search only talks about the move it is on and about windows that
+///failed once it has run this long.
+const INFO_AFTER: Duration = Duration::from_secs(1);
+///How many nodes a thread searches between looks at the clock and the
+///node limit.
+const CHECK_EVERY: u64 = 1024;
+///A capture is skipped in quiescence if even this much on top of the
+///captured piece doesn't bring the score up to alpha.
+const DELTA_MARGIN: Score = 200;
+///Half the width of the first aspiration window.
+const ASPIRATION_WINDOW: Score = 25;
+///Iterations shallower than this use the full window.
+const ASPIRATION_DEPTH: u32 = 4;
+///Give up on aspiration windows once they get this wide.
+const ASPIRATION_MAX: Score = 800;
+///The selective techniques only apply this close to the horizon.
+const REVERSE_FUTILITY_DEPTH: u32 = 6;
+const RAZOR_DEPTH: u32 = 2;
+const FUTILITY_DEPTH: u32 = 3;
+const LATE_MOVE_PRUNING_DEPTH: u32 = 4;
+///Null moves and reductions only this far from it.
+const NULL_MOVE_DEPTH: u32 = 3;
+const LMR_DEPTH: u32 = 3;
+///Moves searched at full depth before the reductions start.
+const LMR_FULL_MOVES: usize = 3;
+///Full strength, anything lower makes the engine play worse on purpose.
+pub const MAX_SKILL_LEVEL: u32 = 20;
+///Lines searched to pick a worse move from at lower skill levels.
+const SKILL_LINES: usize = 4;
 
-// fn negamax(position: &Chessboard, should_stop: Arc<AtomicBool>, alpha: f64, beta: f64, depth: u32) ->  f64 {
-    
-//     if should_stop.load(Ordering::Relaxed) {
-//         return MIN_SCORE
-//     }
+///Settings that stay the same from one search to the next.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchOptions {
+    ///Time kept in reserve on the clock for communication delays.
+    pub move_overhead: Duration,
+    ///Skip captures that lose material in quiescence search.
+    pub qsearch_see: bool,
+    ///How many threads search at once.
+    pub threads: usize,
+    ///How many of the best root moves get a line of their own.
+    pub multipv: usize,
+    ///Below `MAX_SKILL_LEVEL` the search stops early and plays a random
+    ///move among the best few, the worse the lower the level.
+    pub skill_level: u32,
+    ///Order moves with `MovePicker`. Only turned off to measure what it
+    ///saves.
+    pub move_ordering: bool,
+    // The selective search parameters below turn their technique off at 0.
+    ///Plies a null move search is reduced by, on top of one more every six
+    ///plies of depth.
+    pub null_move_reduction: u32,
+    ///Late move reductions are `ln(depth) * ln(move number)` divided by
+    ///this, in hundredths.
+    pub lmr_divisor: u32,
+    ///Reverse futility pruning: a node fails high if its static evaluation
+    ///beats beta by this many centipawns per ply of depth.
+    pub reverse_futility_margin: Score,
+    ///Futility pruning: quiet moves are skipped if the static evaluation
+    ///plus this many centipawns per ply of depth doesn't reach alpha.
+    pub futility_margin: Score,
+    ///Razoring: a node drops into quiescence search if its static
+    ///evaluation plus this many centipawns per ply of depth is below alpha.
+    pub razor_margin: Score,
+    ///Late move pruning: quiet moves after the first `this + depth²` are
+    ///skipped.
+    pub late_move_pruning: u32,
+    pub eval: EvalParams,
+}
 
-//     if depth == 0 {
-//         return evaluate(position)
-//     }
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            move_overhead: Duration::from_millis(30),
+            qsearch_see: true,
+            threads: 1,
+            multipv: 1,
+            skill_level: MAX_SKILL_LEVEL,
+            move_ordering: true,
+            null_move_reduction: 3,
+            lmr_divisor: 225,
+            reverse_futility_margin: 80,
+            futility_margin: 100,
+            razor_margin: 300,
+            late_move_pruning: 3,
+            eval: EvalParams::default(),
+        }
+    }
+}
 
-//     let legal_moves = position.legal_moves();
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    ///Deepest iteration that finished.
+    pub depth: u32,
+    ///Deepest ply that iteration reached, quiescence included.
+    pub seldepth: u32,
+    ///Nodes visited, quiescence search included.
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
 
-//     if legal_moves.len() == 0 {
-//         match position.position.in_check() {
-//             true => return -1000.0, 
-//             false => return 0.0,
-//         }
-//     }
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchResult {
+    ///The best move of the last iteration that finished, `None` if there
+    ///are no legal moves.
+    pub best: Option<Move>,
+    ///The reply the search expects to `best`.
+    pub ponder: Option<Move>,
+    ///From the side to move's point of view.
+    pub score: Score,
+    ///The principal variation, starting with `best`.
+    pub pv: Vec<Move>,
+    pub stats: SearchStats,
+}
 
-//     let alpha = Arc::new(Mutex::new(alpha.max(MIN_SCORE)));
-//     let cut_off = Arc::new(AtomicBool::new(false));
+///Lets another thread stop a search, or tell it the move it was pondering
+///on was played.
+#[derive(Debug, Default)]
+pub struct SearchControl {
+    stop: AtomicBool,
+    pondering: AtomicBool,
+    ///When `ponderhit` came, the clock counts from there.
+    ponderhit: Mutex<Option<Instant>>,
+}
 
-//     legal_moves.par_iter().for_each(|mv| {
-//         let cut_off = Arc::clone(&cut_off); 
-        
-//         if should_stop.load(Ordering::Relaxed) {
-//             cut_off.store(true, Ordering::Relaxed);
-//             return
-//         }
+impl SearchControl {
+    ///A search started with `go ponder` ignores the clock until `ponderhit`.
+    pub fn new(pondering: bool) -> SearchControl {
+        SearchControl { pondering: AtomicBool::new(pondering), ..SearchControl::default() }
+    }
 
-//         if cut_off.load(Ordering::Relaxed) {
-//             return
-//         }
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
 
-//         let score = {
-//             let alpha = *Arc::clone(&alpha).lock().unwrap();
-//             -negamax(&position.make_move(*mv), Arc::clone(&cut_off), -beta, -alpha, depth-1)
-//         };
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
 
-//         let alpha = Arc::clone(&alpha);
-//         let mut alpha = alpha.lock().unwrap();
+    ///The ponder move was played, the clock counts from now on.
+    pub fn ponderhit(&self) {
+        *self.ponderhit.lock().unwrap() = Some(Instant::now());
+        self.pondering.store(false, Ordering::Release);
+    }
 
-//         *alpha = alpha.max(score);
-//         if *alpha >= beta {
-//             cut_off.store(true, Ordering::Relaxed);
-//         }
-//     });
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Acquire)
+    }
 
-//     let best_score = *alpha.lock().unwrap();
-//     best_score
-// }
+    fn ponderhit_at(&self) -> Option<Instant> {
+        *self.ponderhit.lock().unwrap()
+    }
+}
 
-fn negamax(position: &Chessboard, alpha: f64, beta: f64, depth: u32) ->  f64 {
-    
-    if depth == 0 {
-        return evaluate(position)
+///What the search has to say while it runs, for the GUI.
+#[derive(Copy, Clone, Debug)]
+pub enum Info<'a> {
+    ///An iteration finished the `multipv`th best line, counting from 1, with
+    ///an exact score, or the score fell outside the aspiration window and
+    ///`bound` says on which side.
+    Iteration {
+        multipv: usize,
+        score: Score,
+        bound: Bound,
+        pv: &'a [Move],
+        stats: SearchStats,
+        ///Permille of the transposition table in use.
+        hashfull: u32,
+    },
+    ///The root move the iteration at `depth` is on, `number` counting from 1.
+    CurrentMove { depth: u32, mv: Move, number: usize },
+}
+
+///Everything the threads searching one position share.
+struct Search<'a> {
+    tt: &'a TranspositionTable,
+    options: &'a SearchOptions,
+    control: &'a SearchControl,
+    report: &'a (dyn Fn(Info) + Sync),
+    time: TimeManager,
+    node_limit: Option<u64>,
+    ///Lines the main thread searches, the others only search the best.
+    lines: usize,
+    nodes: AtomicU64,
+    ///Raised by the main thread when it is done, `control` is for the
+    ///caller.
+    stop: AtomicBool,
+    reductions: Box<[[u8; 64]; 64]>,
+}
+
+impl Search<'_> {
+    fn new<'a>(tt: &'a TranspositionTable, options: &'a SearchOptions, control: &'a SearchControl,
+               report: &'a (dyn Fn(Info) + Sync), time: TimeManager, node_limit: Option<u64>,
+               lines: usize) -> Search<'a> {
+        Search {
+            tt,
+            options,
+            control,
+            report,
+            time,
+            node_limit,
+            lines,
+            nodes: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+            reductions: reduction_table(options.lmr_divisor),
+        }
     }
 
-    let legal_moves = position.legal_moves();
+    ///Adds nodes a thread searched to the total and raises the stop flag
+    ///if a limit was hit.
+    fn count_nodes(&self, nodes: u64) {
+        let total = self.nodes.fetch_add(nodes, Ordering::Relaxed) + nodes;
+        let out_of_time = self.clock().is_some_and(|clock| clock.hard_limit_reached());
+        if self.node_limit.is_some_and(|limit| total >= limit) || out_of_time {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
 
-    if legal_moves.len() == 0 {
-        match position.position.in_check() {
-            true => return -1000.0, 
-            false => return 0.0,
+    ///The time manager for the move, counting from `ponderhit` if the search
+    ///pondered. `None` while it still ponders.
+    fn clock(&self) -> Option<TimeManager> {
+        if self.control.is_pondering() {
+            return None;
+        }
+        match self.control.ponderhit_at() {
+            Some(ponderhit) => Some(self.time.restarted(ponderhit)),
+            None => Some(self.time.clone()),
         }
     }
 
-    let mut alpha = alpha.max(MIN_SCORE);
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed) || self.control.is_stopped()
+    }
+
+    ///How much less deep the `index`th move is searched at `depth`.
+    fn reduction(&self, depth: u32, index: usize) -> u32 {
+        self.reductions[(depth as usize).min(63)][index.min(63)] as u32
+    }
+}
 
-    for mv in legal_moves {
-        alpha = alpha.max(-negamax(&position.make_move(mv), -beta, -alpha, depth-1));
-        if alpha >= beta {
-            break;
+///`ln(depth) * ln(move number) / divisor` for depths and move numbers up
+///to 63, all zeros if `divisor` is.
+fn reduction_table(divisor: u32) -> Box<[[u8; 64]; 64]> {
+    let mut table = Box::new([[0; 64]; 64]);
+    if divisor == 0 {
+        return table;
+    }
+    for (depth, row) in table.iter_mut().enumerate().skip(1) {
+        for (index, reduction) in row.iter_mut().enumerate().skip(1) {
+            let r = (depth as f64).ln() * (index as f64).ln() * 100.0 / divisor as f64;
+            *reduction = r as u8;
         }
     }
+    table
+}
 
-    alpha
+///Whether `color` has anything besides pawns and the king. Without it
+///zugzwang is common and passing is often the best move, so null moves
+///would prove nothing.
+fn has_pieces(position: &Chessboard, color: Color) -> bool {
+    let pieces = &position.position.board()[color];
+    !(pieces[Piece::Knight] | pieces[Piece::Bishop] | pieces[Piece::Rook] | pieces[Piece::Queen]).is_empty()
 }
 
+///The state of one search thread.
+struct Worker<'a> {
+    search: &'a Search<'a>,
+    heuristics: Box<Heuristics>,
+    ///Triangular PV table: `pv[ply]` is the best line found from `ply` on,
+    ///built from the move played there and `pv[ply + 1]`.
+    pv: Vec<Vec<Move>>,
+    ///The principal variation of the previous iteration, searched first.
+    previous_pv: Vec<Move>,
+    ///How many plies of the current line match `previous_pv`.
+    following: u32,
+    ///0 for the main thread.
+    index: usize,
+    nodes: u64,
+    seldepth: u32,
+}
 
-pub fn best_move(chessboard: &Chessboard, depth: u32) -> Move {
-    let legal_moves = chessboard.legal_moves();
+impl<'a> Worker<'a> {
+    fn new(search: &'a Search<'a>, index: usize) -> Worker<'a> {
+        Worker {
+            search,
+            index,
+            heuristics: Heuristics::new(),
+            pv: vec![Vec::new(); MAX_PLY as usize + 1],
+            previous_pv: Vec::new(),
+            following: 0,
+            nodes: 0,
+            seldepth: 0,
+        }
+    }
+
+    fn visit(&mut self, ply: u32) {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        if self.nodes.is_multiple_of(CHECK_EVERY) {
+            self.search.count_nodes(CHECK_EVERY);
+        }
+    }
+
+    fn picker(&self, position: &Chessboard, hash_move: Option<Move>, ply: u32, previous: Option<Move>) -> MovePicker {
+        match self.search.options.move_ordering {
+            true => MovePicker::new(&position.position, hash_move, ply, previous, &self.heuristics),
+            false => MovePicker::unordered(&position.position, hash_move),
+        }
+    }
+
+    ///Searches `child`, reached by playing `mv` at `ply`, with `negamax`,
+    ///keeping track of whether the line still follows the previous
+    ///principal variation.
+    fn search_child(&mut self, child: &Chessboard, mv: Move, alpha: Score, beta: Score, depth: u32,
+                    ply: u32) -> Score {
+        let following = self.following;
+        if following == ply && self.previous_pv.get(ply as usize) == Some(&mv) {
+            self.following = ply + 1;
+        }
+        let score = -self.negamax(child, -beta, -alpha, depth, ply + 1, Some(mv));
+        self.following = following;
+        score
+    }
+
+    ///`pv[ply]` becomes `mv` followed by the line below it.
+    fn update_pv(&mut self, ply: u32, mv: Move) {
+        let (line, below) = self.pv.split_at_mut(ply as usize + 1);
+        let line = &mut line[ply as usize];
+        line.clear();
+        line.push(mv);
+        line.extend_from_slice(&below[0]);
+    }
+
+    ///Principal variation search: the first move gets the full window, the
+    ///others a null window that only proves they are no better, and they
+    ///are searched again in full if they are. Away from the principal
+    ///variation, nodes and moves that look hopeless or won are cut short,
+    ///see `SearchOptions`. Fail-soft. `ply` is the distance from the root
+    ///and `previous` the move that led here, `None` after a null move. The
+    ///score is meaningless once the search is stopped.
+    fn negamax(&mut self, position: &Chessboard, mut alpha: Score, beta: Score, depth: u32, ply: u32,
+               previous: Option<Move>) -> Score {
+        let search = self.search;
+        self.pv[ply as usize].clear();
+        self.visit(ply);
+        if search.stopped() {
+            return 0
+        }
+        // The boards carry the game's history, so positions from before the
+        // search count too. A single repetition is scored as a draw: the
+        // side that allowed it can repeat it again.
+        if position.repetitions() > 1 || (position.position.half_moves >= 100 && !is_mate(position)) {
+            return 0
+        }
+
+        let pv_node = beta - alpha > 1;
+        let hash = position.hash();
+        let entry = search.tt.probe(hash);
+        if let Some(entry) = entry {
+            let score = entry.score(ply);
+            // Cutting a PV node short would cut its line from the PV too.
+            if !pv_node && entry.depth as u32 >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(position, alpha, beta, ply)
+        }
+
+        let options = search.options;
+        let in_check = position.position.in_check();
+        let static_eval = match in_check {
+            true => -INFINITY,
+            false => evaluate_cp(position, &search.options.eval),
+        };
+
+        if !pv_node && !in_check {
+            let margin = options.reverse_futility_margin;
+            if margin > 0 && depth <= REVERSE_FUTILITY_DEPTH && beta.abs() < MATE_BOUND &&
+               static_eval - margin * depth as Score >= beta {
+                return static_eval
+            }
+
+            let margin = options.razor_margin;
+            if margin > 0 && depth <= RAZOR_DEPTH && static_eval + margin * depth as Score <= alpha {
+                let score = self.quiescence(position, alpha, alpha + 1, ply);
+                if score <= alpha {
+                    return score
+                }
+            }
+
+            // `previous` is `None` right after a null move, two in a row
+            // would prove nothing.
+            if options.null_move_reduction > 0 && depth >= NULL_MOVE_DEPTH && previous.is_some() &&
+               static_eval >= beta && has_pieces(position, position.position.turn) {
+                let reduction = options.null_move_reduction + depth / 6;
+                let child = position.make_null_move();
+                let score = -self.negamax(&child, -beta, -beta + 1, depth.saturating_sub(1 + reduction), ply + 1, None);
+                if search.stopped() {
+                    return 0
+                }
+                if score >= beta {
+                    // Don't trust a mate found by passing.
+                    return if score >= MATE_BOUND { beta } else { score }
+                }
+            }
+        }
+
+        let pv_move = match self.following == ply {
+            true => self.previous_pv.get(ply as usize).copied(),
+            false => None,
+        };
+        let hash_move = pv_move.or_else(|| entry.and_then(|entry| entry.best_move));
+        let moves = self.picker(position, hash_move, ply, previous);
+
+        if moves.is_empty() {
+            match in_check {
+                true => return ply as Score - MATE,
+                false => return 0,
+            }
+        }
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut quiets_tried = MoveList::new();
+
+        for (index, mv) in moves.enumerate() {
+            let quiet = is_quiet(&position.position, mv);
+            let child = position.make_move(mv);
+            let gives_check = child.position.in_check();
+            let tactical = !quiet || in_check || gives_check;
+
+            // Once a move has saved the node from being mated, quiet moves
+            // that can't raise alpha are skipped.
+            if !pv_node && !tactical && best_score > -MATE_BOUND {
+                let late = options.late_move_pruning > 0 &&
+                           quiets_tried.len() as u32 >= options.late_move_pruning + depth * depth;
+                if depth <= LATE_MOVE_PRUNING_DEPTH && late {
+                    continue;
+                }
+                let futile = options.futility_margin > 0 &&
+                             static_eval + options.futility_margin * depth as Score <= alpha;
+                if depth <= FUTILITY_DEPTH && futile {
+                    continue;
+                }
+            }
+
+            let score = if index == 0 {
+                self.search_child(&child, mv, alpha, beta, depth-1, ply)
+            } else {
+                // Late quiet moves rarely turn out best, so they are first
+                // searched less deep.
+                let mut reduction = 0;
+                if depth >= LMR_DEPTH && index >= LMR_FULL_MOVES && !tactical {
+                    reduction = search.reduction(depth, index) + !pv_node as u32;
+                    reduction = reduction.min(depth - 2);
+                }
+                let mut score = self.search_child(&child, mv, alpha, alpha + 1, depth-1-reduction, ply);
+                if reduction > 0 && score > alpha {
+                    score = self.search_child(&child, mv, alpha, alpha + 1, depth-1, ply);
+                }
+                match score > alpha && score < beta {
+                    true => self.search_child(&child, mv, alpha, beta, depth-1, ply),
+                    false => score,
+                }
+            };
+
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    best_move = Some(mv);
+                    self.update_pv(ply, mv);
+                }
+                if alpha >= beta {
+                    if quiet && !search.stopped() {
+                        self.heuristics.update(&position.position, ply, depth, mv, previous, &quiets_tried);
+                    }
+                    break;
+                }
+            }
+            if quiet {
+                quiets_tried.push(mv);
+            }
+        }
+
+        if search.stopped() {
+            return 0
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        search.tt.store(hash, depth, ply, bound, best_score, best_move);
+
+        best_score
+    }
+
+    ///Searches captures and promotions until the position is quiet, so the
+    ///evaluation isn't taken in the middle of an exchange. In check every
+    ///evasion is searched instead.
+    fn quiescence(&mut self, position: &Chessboard, mut alpha: Score, beta: Score, ply: u32) -> Score {
+        let search = self.search;
+        self.pv[ply as usize].clear();
+        self.visit(ply);
+        if search.stopped() {
+            return 0
+        }
+
+        let in_check = position.position.in_check();
+        let moves = position.position.legal_move_list();
+        if moves.is_empty() {
+            match in_check {
+                true => return ply as Score - MATE,
+                false => return 0,
+            }
+        }
+        if ply >= MAX_PLY {
+            return evaluate_cp(position, &search.options.eval)
+        }
+
+        let mut best_score = -INFINITY;
+        let mut stand_pat = -INFINITY;
+        if !in_check {
+            // The side to move can usually do at least as well as doing nothing.
+            stand_pat = evaluate_cp(position, &search.options.eval);
+            if stand_pat >= beta {
+                return stand_pat
+            }
+            alpha = alpha.max(stand_pat);
+            best_score = stand_pat;
+        }
 
-    let best_score = Arc::new(Mutex::new(MIN_SCORE));
-    let mov = Arc::new(Mutex::new(None));
+        let board = &position.position;
+        let mut moves: Vec<(Move, i32)> = moves.into_iter()
+            .filter(|&mv| {
+                if in_check {
+                    return true;
+                }
+                if is_quiet(board, mv) || stand_pat + material_gain(board, mv) + DELTA_MARGIN <= alpha {
+                    return false;
+                }
+                !search.options.qsearch_see || see::see_ge(board, mv, 0)
+            })
+            .map(|mv| (mv, mvv_lva(board, mv)))
+            .collect();
+        moves.sort_by_key(|&(_, order)| -order);
 
-    let beta = MAX_SCORE;
+        for (mv, _) in moves {
+            let score = -self.quiescence(&position.make_move(mv), -beta, -alpha, ply+1);
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                }
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        best_score
+    }
+}
+
+///What an iteration found at the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Iteration {
+    depth: u32,
+    seldepth: u32,
+    score: Score,
+    ///Starts with the best move.
+    pv: Vec<Move>,
+}
+
+impl Worker<'_> {
+    fn is_main(&self) -> bool {
+        self.index == 0
+    }
+
+    ///Reports `iteration` as the `line`th best if this is the main thread,
+    ///with the nodes searched so far by every thread. The extra lines a
+    ///weaker level searches to pick from aren't reported.
+    fn report(&self, iteration: &Iteration, line: usize, bound: Bound) {
+        let search = self.search;
+        if !self.is_main() || line >= search.options.multipv.max(1) {
+            return;
+        }
+        let stats = SearchStats {
+            depth: iteration.depth,
+            seldepth: iteration.seldepth,
+            nodes: search.nodes.load(Ordering::Relaxed) + self.nodes % CHECK_EVERY,
+            elapsed: search.time.elapsed(),
+        };
+        (search.report)(Info::Iteration {
+            multipv: line + 1,
+            score: iteration.score,
+            bound,
+            pv: &iteration.pv,
+            stats,
+            hashfull: search.tt.hashfull(),
+        });
+    }
+
+    ///One iteration over the root moves with the window `alpha`, `beta`,
+    ///`None` if it was stopped before it finished. If every move fails low,
+    ///the result is the best upper bound.
+    fn search_root(&mut self, chessboard: &Chessboard, moves: &[Move], depth: u32, mut alpha: Score,
+                   beta: Score) -> Option<Iteration> {
+        let mut best: Option<Iteration> = None;
+
+        for (index, &mv) in moves.iter().enumerate() {
+            if self.is_main() && self.search.time.elapsed() >= INFO_AFTER {
+                (self.search.report)(Info::CurrentMove { depth, mv, number: index + 1 });
+            }
+            self.following = 0;
+            let child = chessboard.make_move(mv);
+            let mut score = match index {
+                0 => self.search_child(&child, mv, alpha, beta, depth-1, 0),
+                _ => self.search_child(&child, mv, alpha, alpha + 1, depth-1, 0),
+            };
+            if index > 0 && score > alpha && score < beta {
+                score = self.search_child(&child, mv, alpha, beta, depth-1, 0);
+            }
+            if self.search.stopped() {
+                return None;
+            }
+
+            if best.as_ref().is_none_or(|best| score > best.score) {
+                self.update_pv(0, mv);
+                best = Some(Iteration { depth, seldepth: self.seldepth, score, pv: self.pv[0].clone() });
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        best
+    }
+
+    ///Searches the `line`th best line at `depth` with a window around the
+    ///previous iteration's score, widening it whenever the score falls
+    ///outside, since a narrow window cuts more. `moves` leaves out the
+    ///moves of the better lines.
+    fn aspiration(&mut self, chessboard: &Chessboard, moves: &[Move], depth: u32, line: usize,
+                  previous_score: Score) -> Option<Iteration> {
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = match depth >= ASPIRATION_DEPTH && previous_score.abs() < MATE_BOUND {
+            true => (previous_score - delta, previous_score + delta),
+            false => (-INFINITY, INFINITY),
+        };
+
+        loop {
+            let result = self.search_root(chessboard, moves, depth, alpha, beta)?;
+            let bound = if result.score <= alpha && alpha > -INFINITY {
+                alpha = (result.score - delta).max(-INFINITY);
+                Bound::Upper
+            } else if result.score >= beta && beta < INFINITY {
+                beta = (result.score + delta).min(INFINITY);
+                Bound::Lower
+            } else {
+                return Some(result);
+            };
+            if self.search.time.elapsed() >= INFO_AFTER {
+                self.report(&result, line, bound);
+            }
+            delta *= 2;
+            if delta > ASPIRATION_MAX {
+                alpha = -INFINITY;
+                beta = INFINITY;
+            }
+        }
+    }
 
-    legal_moves.par_iter().for_each(|mv| {
+    ///Iterative deepening up to `max_depth`, returning the lines of the
+    ///last iteration that finished, best first. The main thread decides
+    ///when to stop, stops the others when it does and reports every
+    ///iteration. The others skip some depths, each in its own pattern, so
+    ///they spread out over several depths instead of all searching the
+    ///same tree.
+    fn iterate(&mut self, chessboard: &Chessboard, max_depth: u32) -> Vec<Iteration> {
+        const SKIP_SIZE: [u32; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+        const SKIP_PHASE: [u32; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+        let search = self.search;
+        let main = self.is_main();
 
-        let alpha = *Arc::clone(&best_score).lock().unwrap();
+        let mut moves = chessboard.legal_moves();
+        let lines = if main { search.lines.min(moves.len()) } else { 1 };
+        let mut last: Vec<Iteration> = Vec::new();
+        let mut stability = 0;
 
-        let score = -negamax(&chessboard.make_move(*mv), -beta, -alpha, depth-1);
+        'deepening: for depth in 1..=max_depth {
+            if main && depth > 1 && search.clock().is_some_and(|clock| !clock.start_iteration(stability)) {
+                break;
+            }
+            let skip = (self.index + 19) % 20;
+            if !main && ((depth + SKIP_PHASE[skip]) / SKIP_SIZE[skip]) % 2 == 1 {
+                continue;
+            }
+            self.seldepth = 0;
 
-        let best_score = Arc::clone(&best_score);
-        let mut best_score = best_score.lock().unwrap();
+            let mut found = Vec::with_capacity(lines);
+            for line in 0..lines {
+                let previous = last.get(line);
+                self.previous_pv = previous.map_or(Vec::new(), |previous| previous.pv.clone());
+                let previous_score = previous.map_or(0, |previous| previous.score);
+                let iteration = match self.aspiration(chessboard, &moves[line..], depth, line, previous_score) {
+                    Some(iteration) => iteration,
+                    None => break 'deepening,
+                };
+                // Search it first next time so the other moves get a better
+                // alpha, and leave it out of the lines after this one.
+                let index = moves.iter().position(|&mv| mv == iteration.pv[0]).unwrap();
+                moves[line..=index].rotate_right(1);
+                found.push(iteration);
+            }
+            // A later line can come out better than an earlier one.
+            found.sort_by_key(|line| -line.score);
+            for (line, iteration) in found.iter().enumerate() {
+                moves[line] = iteration.pv[0];
+                self.report(iteration, line, Bound::Exact);
+            }
 
-        let mov = Arc::clone(&mov);
-        let mut mov = mov.lock().unwrap();
+            stability = match last.first() {
+                Some(last) if last.pv[0] == found[0].pv[0] => stability + 1,
+                _ => 0,
+            };
+            last = found;
+        }
 
-        if *best_score < score {
-            *best_score = score;
-            *mov = Some(*mv);
+        if main {
+            search.stop.store(true, Ordering::Relaxed);
         }
+        last
+    }
+}
+
+///Mate on the hundredth half move still counts as mate.
+fn is_mate(chessboard: &Chessboard) -> bool {
+    chessboard.position.in_check() && chessboard.position.legal_move_list().is_empty()
+}
+
+///Picks the move most threads agree on, weighing each thread's vote by how
+///deep it got and how good it thinks the move is, and returns the deepest
+///result for that move.
+fn vote<'a>(results: &[&'a Iteration]) -> Option<&'a Iteration> {
+    let worst = results.iter().map(|result| result.score).min().unwrap_or(0);
+    let votes = |mv: Move| -> i64 {
+        results.iter()
+            .filter(|result| result.pv[0] == mv)
+            .map(|result| (result.score - worst + 20) as i64 * result.depth as i64)
+            .sum()
+    };
+    results.iter()
+        .max_by_key(|result| (votes(result.pv[0]), result.depth, result.score))
+        .copied()
+}
+
+///Picks one of `lines`, best first, the way a weaker player at `level`
+///might: the lower the level, the more the score of each line is pushed
+///by `random` towards the best one and past it.
+fn weaker_line(lines: &[Iteration], level: u32, mut random: impl FnMut() -> u64) -> &Iteration {
+    let weakness = 120 - 2 * level.min(MAX_SKILL_LEVEL) as i64;
+    let top = lines[0].score as i64;
+    let spread = (top - lines[lines.len() - 1].score as i64).min(piece_value(Piece::Pawn) as i64);
+    lines.iter()
+        .map(|line| {
+            let score = line.score as i64;
+            let push = (weakness * (top - score) + spread * (random() % weakness as u64) as i64) / 128;
+            (score + push, line)
+        })
+        .fold(None, |best: Option<(i64, &Iteration)>, (score, line)| match best {
+            Some((best_score, _)) if best_score > score => best,
+            _ => Some((score, line)),
+        })
+        .unwrap().1
+}
+
+///xorshift64, seeded from the clock. Only for picking moves at lower skill
+///levels.
+fn random_numbers() -> impl FnMut() -> u64 {
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let mut state = nanos as u64 | 1;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    }
+}
+
+///Searches with iterative deepening until `limits` or the clock run out,
+///or `control` stops it, passing what it finds along the way to `report`.
+///With several threads, each runs its own iterative deepening and they
+///share what they find through the transposition table.
+pub fn search(chessboard: &Chessboard, limits: &Limits, tt: &TranspositionTable, options: &SearchOptions,
+              control: &SearchControl, report: &(dyn Fn(Info) + Sync)) -> SearchResult {
+    let weaker = options.skill_level < MAX_SKILL_LEVEL;
+    let (lines, max_depth) = match weaker {
+        true => (options.multipv.max(SKILL_LINES), limits.depth.unwrap_or(MAX_DEPTH).min(1 + options.skill_level)),
+        false => (options.multipv.max(1), limits.depth.unwrap_or(MAX_DEPTH).max(1)),
+    };
+    let time = TimeManager::new(limits, chessboard.position.turn, options.move_overhead);
+    let search = Search::new(tt, options, control, report, time, limits.nodes, lines);
+    tt.new_search();
+
+    let first = match chessboard.legal_moves().first() {
+        Some(&mv) => mv,
+        None => return SearchResult { best: None, ponder: None, score: 0, pv: Vec::new(), stats: SearchStats::default() },
+    };
+
+    let finished: Vec<(Vec<Iteration>, u64)> = thread::scope(|scope| {
+        let threads: Vec<_> = (0..options.threads.max(1)).map(|index| {
+            let search = &search;
+            thread::Builder::new()
+                .stack_size(THREAD_STACK_SIZE)
+                .spawn_scoped(scope, move || {
+                    let mut worker = Worker::new(search, index);
+                    let lines = worker.iterate(chessboard, max_depth);
+                    (lines, worker.nodes)
+                })
+                .expect("couldn't start a search thread")
+        }).collect();
+        threads.into_iter().map(|thread| thread.join().unwrap()).collect()
     });
-    let mov = *mov.lock().unwrap();
-    mov.unwrap()
+
+    let nodes = finished.iter().map(|&(_, nodes)| nodes).sum();
+    let stats = SearchStats { nodes, elapsed: search.time.elapsed(), ..SearchStats::default() };
+    let main = &finished[0].0;
+    let best = if weaker && !main.is_empty() {
+        Some(weaker_line(main, options.skill_level, random_numbers()))
+    } else if lines > 1 {
+        // The other threads only know the best line.
+        main.first()
+    } else {
+        vote(&finished.iter().filter_map(|(lines, _)| lines.first()).collect::<Vec<_>>())
+    };
+    let best = match best {
+        Some(best) => best,
+        // Not even depth 1 finished.
+        None => return SearchResult { best: Some(first), ponder: None, score: 0, pv: vec![first], stats },
+    };
+
+    let stats = SearchStats { depth: best.depth, seldepth: best.seldepth, ..stats };
+    if main.first() != Some(best) {
+        // The GUI should see the line of the move played.
+        report(Info::Iteration {
+            multipv: 1,
+            score: best.score,
+            bound: Bound::Exact,
+            pv: &best.pv,
+            stats,
+            hashfull: tt.hashfull(),
+        });
+    }
+    SearchResult {
+        best: Some(best.pv[0]),
+        ponder: best.pv.get(1).copied(),
+        score: best.score,
+        pv: best.pv.clone(),
+        stats,
+    }
+}
+
+///Searches exactly `depth` plies.
+pub fn best_move(chessboard: &Chessboard, depth: u32) -> Move {
+    let tt = TranspositionTable::new(DEFAULT_HASH_MB);
+    let options = SearchOptions { move_overhead: Duration::ZERO, ..SearchOptions::default() };
+    search(chessboard, &Limits::depth(depth), &tt, &options, &SearchControl::default(), &|_| {}).best.expect("no legal moves")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::repr::Square;
+
+    #[test]
+    fn stops_on_time_with_a_move() {
+        let chessboard = Chessboard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let limits = Limits::from_go(&["movetime", "200"]);
+        let start = std::time::Instant::now();
+        let tt = TranspositionTable::new(1);
+        let mv = search(&chessboard, &limits, &tt, &SearchOptions::default(), &SearchControl::default(), &|_| {}).best.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(chessboard.legal_moves().contains(&mv));
+
+        let mate = Chessboard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(best_move(&mate, 3).to_string(), "a1a8");
+    }
+
+    #[test]
+    fn draws_in_the_tree() {
+        // A rook up, but every move reaches the fifty move limit.
+        let chessboard = Chessboard::from_fen("7k/8/8/8/8/8/8/R6K w - - 99 80").unwrap();
+        let tt = TranspositionTable::new(1);
+        let result = search(&chessboard, &Limits::depth(3), &tt, &SearchOptions::default(), &SearchControl::default(), &|_| {});
+        assert_eq!(result.score, 0);
+
+        // The same position without the clock, and then after it came
+        // round a second time.
+        let fresh = Chessboard::from_fen("7k/8/8/8/8/8/8/R6K w - - 0 1").unwrap();
+        let repeated = ["a1a2", "h8g8", "a2a1", "g8h8"].iter()
+            .fold(fresh.clone(), |board, mv| board.make_str_move(mv).unwrap());
+        let (options, control) = (SearchOptions::default(), SearchControl::default());
+        let time = TimeManager::new(&Limits::depth(3), Color::White, Duration::ZERO);
+        let search = Search::new(&tt, &options, &control, &|_| {}, time, None, 1);
+        let mut worker = Worker::new(&search, 0);
+        assert!(worker.negamax(&fresh, -INFINITY, INFINITY, 3, 1, None) > 300);
+        assert_eq!(worker.negamax(&repeated, -INFINITY, INFINITY, 3, 1, None), 0);
+    }
+
+    #[test]
+    fn quiescence_sees_recaptures() {
+        let chessboard = Chessboard::from_fen("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_ne!(best_move(&chessboard, 1).to_string(), "d1d5");
+        let chessboard = Chessboard::from_fen("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(best_move(&chessboard, 1).to_string(), "d1d5");
+    }
+
+    #[test]
+    fn principal_variation() {
+        let chessboard = Chessboard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let result = search(&chessboard, &Limits::depth(5), &tt, &SearchOptions::default(), &SearchControl::default(), &|_| {});
+        assert_eq!(result.stats.depth, 5);
+        assert_eq!(result.pv.first().copied(), result.best);
+        assert!(result.pv.len() >= 2);
+        let mut position = chessboard;
+        for &mv in result.pv.iter() {
+            assert!(position.legal_moves().contains(&mv), "{} in {:?} is illegal", mv, result.pv);
+            position = position.make_move(mv);
+        }
+
+        // Mate in two: a quiet rook move, any reply, then mate.
+        let chessboard = Chessboard::from_fen("7k/8/5K2/8/8/8/8/R7 w - - 0 1").unwrap();
+        let result = search(&chessboard, &Limits::depth(4), &tt, &SearchOptions::default(), &SearchControl::default(), &|_| {});
+        assert_eq!(result.score, MATE - 3);
+        assert_eq!(result.pv.len(), 3);
+    }
+
+    #[test]
+    fn selective_search() {
+        let chessboard = Chessboard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let full_width = SearchOptions {
+            null_move_reduction: 0,
+            lmr_divisor: 0,
+            reverse_futility_margin: 0,
+            futility_margin: 0,
+            razor_margin: 0,
+            late_move_pruning: 0,
+            ..SearchOptions::default()
+        };
+        let nodes = |options: &SearchOptions| {
+            search(&chessboard, &Limits::depth(5), &TranspositionTable::new(1), options, &SearchControl::default(), &|_| {}).stats.nodes
+        };
+        assert!(nodes(&SearchOptions::default()) < nodes(&full_width));
+
+        // No null moves in pawn endgames, where zugzwang is common.
+        let pawns = Chessboard::from_fen("8/8/8/1p6/1P6/1K6/8/k7 b - - 0 1").unwrap();
+        assert!(!has_pieces(&pawns, Color::White));
+        assert!(has_pieces(&chessboard, Color::White));
+    }
+
+    #[test]
+    fn controlled_from_another_thread() {
+        let chessboard = Chessboard::starting();
+        let tt = TranspositionTable::new(1);
+        let options = SearchOptions { threads: 2, ..SearchOptions::default() };
+        let start = std::time::Instant::now();
+        let wait_then = |limits: &Limits, control: &SearchControl, signal: fn(&SearchControl)| {
+            thread::scope(|scope| {
+                scope.spawn(|| {
+                    thread::sleep(Duration::from_millis(300));
+                    signal(control);
+                });
+                search(&chessboard, limits, &tt, &options, control, &|_| {})
+            })
+        };
+
+        let result = wait_then(&Limits::from_go(&["infinite"]), &SearchControl::default(), SearchControl::stop);
+        assert!(result.best.is_some());
+        assert!(start.elapsed() >= Duration::from_millis(300));
+
+        // Pondering ignores the clock until the ponder move is played.
+        let start = std::time::Instant::now();
+        let limits = Limits::from_go(&["ponder", "movetime", "100"]);
+        wait_then(&limits, &SearchControl::new(true), SearchControl::ponderhit);
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn clock_starts_at_ponderhit() {
+        let tt = TranspositionTable::new(1);
+        let options = SearchOptions::default();
+        let control = SearchControl::new(true);
+        let time = TimeManager::new(&Limits::from_go(&["ponder", "movetime", "100"]), Color::White, Duration::ZERO);
+        let search = Search::new(&tt, &options, &control, &|_| {}, time, None, 1);
+        thread::sleep(Duration::from_millis(150));
+        assert!(search.clock().is_none());
+        control.ponderhit();
+        assert!(!search.clock().unwrap().hard_limit_reached());
+        thread::sleep(Duration::from_millis(100));
+        assert!(search.clock().unwrap().hard_limit_reached());
+    }
+
+    #[test]
+    fn multipv_and_skill() {
+        let chessboard = Chessboard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let options = SearchOptions { multipv: 3, ..SearchOptions::default() };
+        let lines = std::sync::Mutex::new(Vec::new());
+        let report = |info: Info| if let Info::Iteration { multipv, score, pv, stats, .. } = info {
+            lines.lock().unwrap().push((stats.depth, multipv, score, pv[0]));
+        };
+        let result = search(&chessboard, &Limits::depth(4), &tt, &options, &SearchControl::default(), &report);
+        let lines: Vec<_> = lines.into_inner().unwrap().into_iter().filter(|line| line.0 == 4).collect();
+        assert_eq!(lines.iter().map(|line| line.1).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(lines[0].2 >= lines[1].2 && lines[1].2 >= lines[2].2);
+        assert!(lines[0].3 != lines[1].3 && lines[1].3 != lines[2].3 && lines[0].3 != lines[2].3);
+        assert_eq!(result.best, Some(lines[0].3));
+
+        let options = SearchOptions { skill_level: 0, ..SearchOptions::default() };
+        let reported = std::sync::Mutex::new(Vec::new());
+        let report = |info: Info| if let Info::Iteration { multipv, .. } = info {
+            reported.lock().unwrap().push(multipv);
+        };
+        let result = search(&chessboard, &Limits::depth(8), &tt, &options, &SearchControl::default(), &report);
+        assert!(chessboard.legal_moves().contains(&result.best.unwrap()));
+        assert_eq!(result.stats.depth, 1);
+        // The lines it picks from stay internal, the GUI asked for one.
+        let reported = reported.into_inner().unwrap();
+        assert!(!reported.is_empty() && reported.iter().all(|&multipv| multipv == 1));
+
+        // Full strength always keeps the best line, a low level can trade it
+        // for a worse one.
+        let e4 = Move::Normal(Square::E2, Square::E4, Piece::Pawn);
+        let d4 = Move::Normal(Square::D2, Square::D4, Piece::Pawn);
+        let c4 = Move::Normal(Square::C2, Square::C4, Piece::Pawn);
+        let lines = [
+            Iteration { depth: 5, seldepth: 5, score: 50, pv: vec![e4] },
+            Iteration { depth: 5, seldepth: 5, score: 40, pv: vec![d4] },
+            Iteration { depth: 5, seldepth: 5, score: 10, pv: vec![c4] },
+        ];
+        assert_eq!(weaker_line(&lines, MAX_SKILL_LEVEL, || u64::MAX).pv[0], e4);
+        let mut random = [0, 119, 0].iter().copied();
+        assert_eq!(weaker_line(&lines, 0, || random.next().unwrap()).pv[0], d4);
+    }
+
+    #[test]
+    fn lazy_smp() {
+        let options = SearchOptions { threads: 4, ..SearchOptions::default() };
+        let tt = TranspositionTable::new(1);
+        let chessboard = Chessboard::from_fen("7k/8/5K2/8/8/8/8/R7 w - - 0 1").unwrap();
+        let result = search(&chessboard, &Limits::depth(4), &tt, &options, &SearchControl::default(), &|_| {});
+        assert_eq!(result.score, MATE - 3);
+
+        let chessboard = Chessboard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let limits = Limits::from_go(&["movetime", "200"]);
+        let start = std::time::Instant::now();
+        let result = search(&chessboard, &limits, &tt, &options, &SearchControl::default(), &|_| {});
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(chessboard.legal_moves().contains(&result.best.unwrap()));
+
+
+        // Two shallower threads agreeing outvote a deeper one.
+        let e4 = Move::Normal(Square::E2, Square::E4, Piece::Pawn);
+        let d4 = Move::Normal(Square::D2, Square::D4, Piece::Pawn);
+        let results = [
+            Iteration { depth: 10, seldepth: 10, score: 30, pv: vec![e4] },
+            Iteration { depth: 9, seldepth: 9, score: 30, pv: vec![d4] },
+            Iteration { depth: 8, seldepth: 8, score: 25, pv: vec![d4] },
+        ];
+        let results: Vec<&Iteration> = results.iter().collect();
+        assert_eq!(vote(&results).unwrap().pv, vec![d4]);
+        assert_eq!(vote(&results[..2]).unwrap().pv, vec![e4]);
+    }
 }
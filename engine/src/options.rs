@@ -0,0 +1,194 @@
+//!UCI options. Each option is registered once with its type, and that
+//!drives both the `option` lines sent after `uci` and the checking of
+//!`setoption` values before they reach the engine.
+use std::fmt;
+
+///The type of an option, its default and what setting it does to `S`.
+pub enum OptionKind<S> {
+    Spin { default: i64, min: i64, max: i64, set: fn(&mut S, i64) },
+    Check { default: bool, set: fn(&mut S, bool) },
+    ///One of `choices`, passed to `set` spelled the way they are.
+    Combo { default: &'static str, choices: &'static [&'static str], set: fn(&mut S, &str) },
+    String { default: &'static str, set: fn(&mut S, &str) },
+    ///Has no value, setting it does something.
+    Button { press: fn(&mut S) },
+}
+
+pub struct UciOption<S> {
+    pub name: &'static str,
+    pub kind: OptionKind<S>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionError {
+    Unknown(String),
+    ///The value doesn't fit the type of the option or is out of its range.
+    InvalidValue { name: &'static str, value: Option<String> },
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionError::Unknown(name) => write!(f, "no option named {}", name),
+            OptionError::InvalidValue { name, value: Some(value) } => write!(f, "{} is not a valid value for {}", value, name),
+            OptionError::InvalidValue { name, value: None } => write!(f, "{} needs a value", name),
+        }
+    }
+}
+
+///How UCI writes the empty string.
+const EMPTY: &str = "<empty>";
+
+impl<S> UciOption<S> {
+    ///The `option` line that tells the GUI about it.
+    pub fn declaration(&self) -> String {
+        let kind = match self.kind {
+            OptionKind::Spin { default, min, max, .. } => format!("spin default {} min {} max {}", default, min, max),
+            OptionKind::Check { default, .. } => format!("check default {}", default),
+            OptionKind::Combo { default, choices, .. } => {
+                let vars: Vec<String> = choices.iter().map(|choice| format!("var {}", choice)).collect();
+                format!("combo default {} {}", default, vars.join(" "))
+            }
+            OptionKind::String { default, .. } => {
+                format!("string default {}", if default.is_empty() { EMPTY } else { default })
+            }
+            OptionKind::Button { .. } => "button".to_string(),
+        };
+        format!("option name {} type {}", self.name, kind)
+    }
+
+    ///Checks `value` and applies it to `state` if it fits.
+    pub fn set(&self, state: &mut S, value: Option<&str>) -> Result<(), OptionError> {
+        let invalid = || OptionError::InvalidValue { name: self.name, value: value.map(str::to_string) };
+        match self.kind {
+            OptionKind::Spin { min, max, set, .. } => {
+                match value.and_then(|value| value.parse::<i64>().ok()) {
+                    Some(value) if (min..=max).contains(&value) => set(state, value),
+                    _ => return Err(invalid()),
+                }
+            }
+            OptionKind::Check { set, .. } => {
+                match value {
+                    Some(value) if value.eq_ignore_ascii_case("true") => set(state, true),
+                    Some(value) if value.eq_ignore_ascii_case("false") => set(state, false),
+                    _ => return Err(invalid()),
+                }
+            }
+            OptionKind::Combo { choices, set, .. } => {
+                let choice = value.and_then(|value| choices.iter().find(|choice| choice.eq_ignore_ascii_case(value)));
+                match choice {
+                    Some(choice) => set(state, choice),
+                    None => return Err(invalid()),
+                }
+            }
+            OptionKind::String { set, .. } => {
+                match value {
+                    None | Some(EMPTY) => set(state, ""),
+                    Some(value) => set(state, value),
+                }
+            }
+            OptionKind::Button { press } => press(state),
+        }
+        Ok(())
+    }
+}
+
+///Every option of an engine whose settings are an `S`.
+pub struct Options<S> {
+    options: Vec<UciOption<S>>,
+}
+
+impl<S> Default for Options<S> {
+    fn default() -> Options<S> {
+        Options { options: Vec::new() }
+    }
+}
+
+impl<S> Options<S> {
+    ///Adds an option. The GUI sees them in the order they were registered.
+    pub fn register(&mut self, name: &'static str, kind: OptionKind<S>) {
+        debug_assert!(self.get(name).is_none(), "{} is registered twice", name);
+        self.options.push(UciOption { name, kind });
+    }
+
+    ///Option names are case-insensitive.
+    pub fn get(&self, name: &str) -> Option<&UciOption<S>> {
+        self.options.iter().find(|option| option.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn declarations(&self) -> impl Iterator<Item = String> + '_ {
+        self.options.iter().map(UciOption::declaration)
+    }
+
+    ///Handles `setoption name <name> [value <value>]`.
+    pub fn set(&self, state: &mut S, name: &str, value: Option<&str>) -> Result<(), OptionError> {
+        match self.get(name) {
+            Some(option) => option.set(state, value),
+            None => Err(OptionError::Unknown(name.to_string())),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Settings {
+        size: i64,
+        fast: bool,
+        style: String,
+        path: String,
+        presses: u32,
+    }
+
+    fn options() -> Options<Settings> {
+        let mut options: Options<Settings> = Options::default();
+        options.register("Size", OptionKind::Spin { default: 16, min: 1, max: 1024, set: |settings, value| settings.size = value });
+        options.register("Fast", OptionKind::Check { default: false, set: |settings, value| settings.fast = value });
+        options.register("Style", OptionKind::Combo {
+            default: "Normal",
+            choices: &["Solid", "Normal", "Risky"],
+            set: |settings, value| settings.style = value.to_string(),
+        });
+        options.register("Book Path", OptionKind::String { default: "", set: |settings, value| settings.path = value.to_string() });
+        options.register("Reset", OptionKind::Button { press: |settings| settings.presses += 1 });
+        options
+    }
+
+    #[test]
+    fn declarations() {
+        let lines: Vec<String> = options().declarations().collect();
+        assert_eq!(lines, [
+            "option name Size type spin default 16 min 1 max 1024",
+            "option name Fast type check default false",
+            "option name Style type combo default Normal var Solid var Normal var Risky",
+            "option name Book Path type string default <empty>",
+            "option name Reset type button",
+        ]);
+    }
+
+    #[test]
+    fn setting_values() {
+        let options = options();
+        let mut settings = Settings::default();
+        options.set(&mut settings, "size", Some("64")).unwrap();
+        options.set(&mut settings, "Fast", Some("true")).unwrap();
+        options.set(&mut settings, "Style", Some("risky")).unwrap();
+        options.set(&mut settings, "Book Path", Some("/books/main.bin")).unwrap();
+        options.set(&mut settings, "Reset", None).unwrap();
+        assert_eq!((settings.size, settings.fast, settings.style.as_str()), (64, true, "Risky"));
+        assert_eq!((settings.path.as_str(), settings.presses), ("/books/main.bin", 1));
+        options.set(&mut settings, "Book Path", Some("<empty>")).unwrap();
+        assert_eq!(settings.path, "");
+
+        let invalid = |name: &'static str, value: &str| OptionError::InvalidValue { name, value: Some(value.to_string()) };
+        assert_eq!(options.set(&mut settings, "Size", Some("0")), Err(invalid("Size", "0")));
+        assert_eq!(options.set(&mut settings, "Size", Some("big")), Err(invalid("Size", "big")));
+        assert_eq!(options.set(&mut settings, "Fast", Some("yes")), Err(invalid("Fast", "yes")));
+        assert_eq!(options.set(&mut settings, "Style", Some("Wild")), Err(invalid("Style", "Wild")));
+        assert_eq!(options.set(&mut settings, "Speed", Some("1")), Err(OptionError::Unknown("Speed".to_string())));
+        assert_eq!(settings.size, 64);
+    }
+}
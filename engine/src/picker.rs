@@ -0,0 +1,252 @@
+//!Move ordering. Alpha-beta cuts the most when the best move is searched
+//!first, so moves come out of the picker in this order:
+//!
+//!1. the move from the transposition table,
+//!2. captures and promotions that don't lose material, most valuable victim
+//!   first and then least valuable attacker,
+//!3. the killer moves of the ply, quiet moves that caused a cutoff in a
+//!   sibling node,
+//!4. the counter-move, the quiet move that last refuted the opponent's move,
+//!5. other quiet moves, by how often they caused cutoffs anywhere,
+//!6. captures that lose material.
+use chess::position::Position;
+use chess::repr::*;
+use chess::repr::move_list::MAX_MOVES;
+use crate::evaluation::{piece_value, Score};
+use crate::see::see_ge;
+use crate::MAX_PLY;
+
+///History scores stay within plus or minus this.
+const MAX_HISTORY: i32 = 16384;
+
+const HASH_MOVE: i32 = 4_000_000;
+const GOOD_CAPTURE: i32 = 3_000_000;
+const KILLER: i32 = 2_000_000;
+const COUNTER_MOVE: i32 = 1_000_000;
+const BAD_CAPTURE: i32 = -1_000_000;
+
+///What a search thread learns about moves as it goes.
+pub struct Heuristics {
+    killers: [[Option<Move>; 2]; MAX_PLY as usize],
+    ///Indexed by the color, piece and destination of the move refuted.
+    counter_moves: [[[Option<Move>; 64]; 6]; 2],
+    ///Indexed by the color, origin and destination of the move.
+    history: [[[i32; 64]; 64]; 2],
+}
+
+impl Heuristics {
+    pub fn new() -> Box<Heuristics> {
+        Box::new(Heuristics {
+            killers: [[None; 2]; MAX_PLY as usize],
+            counter_moves: [[[None; 64]; 6]; 2],
+            history: [[[0; 64]; 64]; 2],
+        })
+    }
+
+    fn history(&self, color: Color, mv: Move) -> i32 {
+        self.history[color as usize][mv.from(color) as usize][mv.to(color) as usize]
+    }
+
+    ///Records the quiet move `best` causing a cutoff at `ply` after
+    ///`previous`, with the quiet moves searched before it that didn't.
+    pub fn update(&mut self, position: &Position, ply: u32, depth: u32, best: Move,
+                  previous: Option<Move>, failed: &[Move]) {
+        let color = position.turn;
+        let killers = &mut self.killers[(ply as usize).min(MAX_PLY as usize - 1)];
+        if killers[0] != Some(best) {
+            killers[1] = killers[0];
+            killers[0] = Some(best);
+        }
+
+        if let Some((piece, square)) = refuted(position, previous) {
+            self.counter_moves[color.other() as usize][piece as usize][square as usize] = Some(best);
+        }
+
+        let bonus = (depth * depth).min(400) as i32;
+        self.add_history(color, best, bonus);
+        for &mv in failed {
+            self.add_history(color, mv, -bonus);
+        }
+    }
+
+    ///Moves the score towards the bound by `bonus`, less and less the closer
+    ///it gets, so the table never saturates and old results fade.
+    fn add_history(&mut self, color: Color, mv: Move, bonus: i32) {
+        let entry = &mut self.history[color as usize][mv.from(color) as usize][mv.to(color) as usize];
+        *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+    }
+}
+
+///The piece the opponent moved with `previous` and where it went.
+fn refuted(position: &Position, previous: Option<Move>) -> Option<(Piece, Square)> {
+    let opponent = position.turn.other();
+    let square = previous?.to(opponent);
+    match position.at(square) {
+        Some((color, piece)) if color == opponent => Some((piece, square)),
+        _ => None,
+    }
+}
+
+///The centipawns `mv` wins outright, ignoring recaptures.
+pub fn material_gain(position: &Position, mv: Move) -> Score {
+    position.captured_piece(mv).map_or(0, piece_value) +
+        mv.promotion().map_or(0, |piece| piece_value(piece) - piece_value(Piece::Pawn))
+}
+
+///How reluctantly a piece captures. The king goes last: it has no material
+///value, but it can only take undefended pieces.
+fn attacker_rank(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 0,
+        Piece::Knight => 1,
+        Piece::Bishop => 2,
+        Piece::Rook => 3,
+        Piece::Queen => 4,
+        Piece::King => 5,
+    }
+}
+
+///Orders captures by the most valuable victim first, then the least
+///valuable attacker.
+pub fn mvv_lva(position: &Position, mv: Move) -> i32 {
+    material_gain(position, mv) * 8 - attacker_rank(position.moved_piece(mv))
+}
+
+///Whether `mv` is searched as a quiet move.
+pub fn is_quiet(position: &Position, mv: Move) -> bool {
+    !position.is_capture(mv) && !mv.is_promotion()
+}
+
+///Hands out the legal moves of a position best first. Moves are scored up
+///front and picked by selection, so a cutoff after the first few moves
+///doesn't pay for sorting the rest.
+pub struct MovePicker {
+    moves: [Move; MAX_MOVES],
+    scores: [i32; MAX_MOVES],
+    len: usize,
+    next: usize,
+}
+
+impl MovePicker {
+    pub fn new(position: &Position, hash_move: Option<Move>, ply: u32, previous: Option<Move>,
+               heuristics: &Heuristics) -> MovePicker {
+        let color = position.turn;
+        let killers = heuristics.killers[(ply as usize).min(MAX_PLY as usize - 1)];
+        let counter_move = refuted(position, previous).and_then(|(piece, square)| {
+            heuristics.counter_moves[color.other() as usize][piece as usize][square as usize]
+        });
+
+        let mut picker = MovePicker { moves: [Move::KingsideCastle; MAX_MOVES], scores: [0; MAX_MOVES], len: 0, next: 0 };
+        for mv in position.legal_move_list() {
+            let score = if Some(mv) == hash_move {
+                HASH_MOVE
+            } else if !is_quiet(position, mv) {
+                let good = see_ge(position, mv, 0);
+                mvv_lva(position, mv) + if good { GOOD_CAPTURE } else { BAD_CAPTURE }
+            } else if killers[0] == Some(mv) {
+                KILLER + 1
+            } else if killers[1] == Some(mv) {
+                KILLER
+            } else if counter_move == Some(mv) {
+                COUNTER_MOVE
+            } else {
+                heuristics.history(color, mv)
+            };
+            picker.moves[picker.len] = mv;
+            picker.scores[picker.len] = score;
+            picker.len += 1;
+        }
+        picker
+    }
+
+    ///The moves in generation order with only the hash move moved to the
+    ///front, the way the search ordered them before the picker.
+    pub fn unordered(position: &Position, hash_move: Option<Move>) -> MovePicker {
+        let mut picker = MovePicker { moves: [Move::KingsideCastle; MAX_MOVES], scores: [0; MAX_MOVES], len: 0, next: 0 };
+        for mv in position.legal_move_list() {
+            picker.moves[picker.len] = mv;
+            picker.scores[picker.len] = if Some(mv) == hash_move { HASH_MOVE } else { -(picker.len as i32) };
+            picker.len += 1;
+        }
+        picker
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Iterator for MovePicker {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        if self.next == self.len {
+            return None;
+        }
+        let best = (self.next..self.len).max_by_key(|&i| (self.scores[i], -(i as i32))).unwrap();
+        self.moves.swap(self.next, best);
+        self.scores.swap(self.next, best);
+        self.next += 1;
+        Some(self.moves[self.next - 1])
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picking_order() {
+        // Rook takes a defended pawn, knight takes an undefended pawn.
+        let position = Position::from_fen("4k3/8/2p5/3p4/p7/2N5/8/3RK3 w - - 0 1").unwrap();
+        let quiet = Move::Normal(Square::E1, Square::F1, Piece::King);
+        let killer = Move::Normal(Square::D1, Square::D2, Piece::Rook);
+        let mut heuristics = Heuristics::new();
+        heuristics.update(&position, 3, 4, killer, None, &[quiet]);
+
+        let hash_move = Move::Normal(Square::C3, Square::E4, Piece::Knight);
+        let moves: Vec<Move> = MovePicker::new(&position, Some(hash_move), 3, None, &heuristics).collect();
+        assert_eq!(moves.len(), position.legal_moves().len());
+        assert_eq!(moves[0], hash_move);
+        assert_eq!(moves[1], Move::Normal(Square::C3, Square::A4, Piece::Knight));
+        assert_eq!(moves[2], killer);
+        assert_eq!(*moves.last().unwrap(), Move::Normal(Square::D1, Square::D5, Piece::Rook));
+        assert!(moves.iter().position(|&mv| mv == quiet) > moves.iter().position(|&mv| mv == Move::Normal(Square::D1, Square::D3, Piece::Rook)));
+
+        // A counter-move answers the same move anywhere in the tree.
+        let previous = Some(Move::Normal(Square::E7, Square::E8, Piece::King));
+        let counter = Move::Normal(Square::D1, Square::D3, Piece::Rook);
+        heuristics.update(&position, 5, 1, counter, previous, &[]);
+        let moves: Vec<Move> = MovePicker::new(&position, None, 9, previous, &heuristics).collect();
+        assert_eq!(moves[0], Move::Normal(Square::C3, Square::A4, Piece::Knight));
+        assert_eq!(moves[1], counter);
+    }
+
+    #[test]
+    fn king_captures_last() {
+        // The pawn on d5 can be taken by the king, the queen or a pawn.
+        let position = Position::from_fen("4k3/8/8/3p4/2PK4/8/8/3Q4 w - - 0 1").unwrap();
+        let by_king = Move::Normal(Square::D4, Square::D5, Piece::King);
+        let by_queen = Move::Normal(Square::D1, Square::D5, Piece::Queen);
+        let by_pawn = Move::Normal(Square::C4, Square::D5, Piece::Pawn);
+        assert!(mvv_lva(&position, by_pawn) > mvv_lva(&position, by_queen));
+        assert!(mvv_lva(&position, by_queen) > mvv_lva(&position, by_king));
+    }
+
+    #[test]
+    fn history_stays_bounded() {
+        let position = Position::starting();
+        let mv = Move::Normal(Square::E2, Square::E4, Piece::Pawn);
+        let mut heuristics = Heuristics::new();
+        for _ in 0..1000 {
+            heuristics.update(&position, 0, 30, mv, None, &[]);
+        }
+        assert!(heuristics.history(Color::White, mv) <= MAX_HISTORY);
+        assert!(heuristics.history(Color::White, mv) > MAX_HISTORY / 2);
+    }
+}
@@ -0,0 +1,103 @@
+//!Static exchange evaluation: what a capture wins once both sides have
+//!traded off every piece attacking the square, cheapest first.
+use chess::position::Position;
+use chess::repr::*;
+use crate::evaluation::{piece_value, Score};
+
+///Whether the legal move `mv` wins at least `threshold` centipawns of
+///material after all the exchanges on its square. Castling never wins or
+///loses material.
+pub fn see_ge(position: &Position, mv: Move, threshold: Score) -> bool {
+    let (from, to) = match mv {
+        Move::KingsideCastle | Move::QueensideCastle => return threshold <= 0,
+        Move::Normal(from, to, _) | Move::EnPassant(from, to) | Move::Promotion(from, to, _) => (from, to),
+    };
+    let board = &position.board();
+
+    // What the side to move is ahead by if the exchange stops now, and what
+    // it loses if it doesn't.
+    let mut swap = position.captured_piece(mv).map_or(0, piece_value) - threshold;
+    let on_square = match mv {
+        Move::Promotion(_, _, piece) => {
+            swap += piece_value(piece) - piece_value(Piece::Pawn);
+            piece
+        }
+        _ => position.moved_piece(mv),
+    };
+    if swap < 0 {
+        return false;
+    }
+    swap = piece_value(on_square) - swap;
+    if swap <= 0 {
+        return true;
+    }
+
+    let mut occupied = board.occupied() - from.to_bitboard();
+    if let Move::EnPassant(..) = mv {
+        occupied.unset(to + Direction::pawn(position.turn).opposite());
+    }
+    let mut turn = position.turn;
+    // Whether the side that made `mv` comes out ahead, flipped with every
+    // capture.
+    let mut wins = true;
+
+    loop {
+        turn = turn.other();
+        // Recomputed every time so sliders behind a capturer join in.
+        let attackers = board.attackers_to(to, occupied) & occupied;
+        let own = attackers & board[turn].occupied();
+        let attacker = match Piece::ALL.iter().copied().find(|&piece| !(own & board[turn][piece]).is_empty()) {
+            Some(piece) => piece,
+            None => break,
+        };
+        wins = !wins;
+
+        if attacker == Piece::King {
+            // The king can only take last.
+            if !(attackers - board[turn].occupied()).is_empty() {
+                wins = !wins;
+            }
+            break;
+        }
+
+        swap = piece_value(attacker) - swap;
+        if swap < wins as Score {
+            break;
+        }
+        occupied.unset((own & board[turn][attacker]).lsb().unwrap());
+    }
+
+    wins
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see(fen: &str, mv: &str, threshold: Score) -> bool {
+        let position = Position::from_fen(fen).unwrap();
+        let mv = position.legal_moves().into_iter().find(|m| m.to_string() == mv).unwrap();
+        see_ge(&position, mv, threshold)
+    }
+
+    #[test]
+    fn exchanges() {
+        // Pawn defended by a pawn: winning it with the rook loses the rook.
+        let fen = "1k6/8/2p5/3p4/8/8/8/K2R4 w - - 0 1";
+        assert!(!see(fen, "d1d5", 0));
+        assert!(see(fen, "d1d5", -400));
+
+        // Undefended pawn.
+        assert!(see("1k6/8/8/3p4/8/8/8/K2R4 w - - 0 1", "d1d5", 100));
+        assert!(!see("1k6/8/8/3p4/8/8/8/K2R4 w - - 0 1", "d1d5", 101));
+
+        // Only the rook behind the first one keeps black from taking back.
+        assert!(see("3r3k/8/8/3p4/8/8/3R4/K2R4 w - - 0 1", "d2d5", 100));
+        assert!(!see("3r3k/8/8/3p4/8/8/3R4/K7 w - - 0 1", "d2d5", 0));
+
+        // The king can only take back an undefended piece.
+        assert!(!see("8/8/4k3/3n4/8/8/8/K2R4 w - - 0 1", "d1d5", 0));
+        assert!(see("8/8/4k3/3n4/8/1B6/8/K2R4 w - - 0 1", "d1d5", 300));
+    }
+}
@@ -0,0 +1,177 @@
+//!Search limits from the UCI `go` command and how much of the clock to
+//!spend on a move.
+use chess::repr::Color;
+use std::time::{Duration, Instant};
+
+///Moves left until the next time control when the GUI doesn't say.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+///The hard limit is this many times the soft one, if the clock allows.
+const HARD_LIMIT_FACTOR: u32 = 4;
+///Never plan to use more than this fraction of the remaining time.
+const MAX_TIME_FRACTION: u32 = 3;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub infinite: bool,
+    ///Search the position after the ponder move until the GUI says
+    ///whether it was played, see `SearchControl`.
+    pub ponder: bool,
+}
+
+impl Limits {
+    pub fn depth(depth: u32) -> Limits {
+        Limits { depth: Some(depth), ..Limits::default() }
+    }
+
+    ///Reads the arguments of `go`. Unknown tokens and values that don't
+    ///parse are skipped.
+    pub fn from_go(args: &[&str]) -> Limits {
+        let mut limits = Limits::default();
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            let mut value = || args.next().and_then(|value| value.parse::<u64>().ok());
+            let millis = |value: Option<u64>| value.map(Duration::from_millis);
+            match arg {
+                "depth" => limits.depth = value().map(|depth| depth as u32),
+                "nodes" => limits.nodes = value(),
+                "movetime" => limits.movetime = millis(value()),
+                "wtime" => limits.wtime = millis(value()),
+                "btime" => limits.btime = millis(value()),
+                "winc" => limits.winc = millis(value()),
+                "binc" => limits.binc = millis(value()),
+                "movestogo" => limits.movestogo = value().map(|moves| moves as u32),
+                "infinite" => limits.infinite = true,
+                "ponder" => limits.ponder = true,
+                _ => {}
+            }
+        }
+        limits
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    ///Don't start another iteration after this, scaled by how settled the
+    ///best move is. `None` for `movetime` and `infinite`, which only stop
+    ///at the hard limit.
+    soft: Option<Duration>,
+    ///Abort the search after this.
+    hard: Option<Duration>,
+}
+
+impl TimeManager {
+    ///`overhead` is subtracted from every budget to cover the time lost
+    ///between the engine and the clock.
+    pub fn new(limits: &Limits, turn: Color, overhead: Duration) -> TimeManager {
+        let start = Instant::now();
+        if limits.infinite {
+            return TimeManager { start, soft: None, hard: None };
+        }
+        // The GUI asked for this much time on the move, so it is used up
+        // however settled the best move is.
+        if let Some(movetime) = limits.movetime {
+            let budget = movetime.saturating_sub(overhead);
+            return TimeManager { start, soft: None, hard: Some(budget) };
+        }
+
+        let (time, increment) = match turn {
+            Color::White => (limits.wtime, limits.winc),
+            Color::Black => (limits.btime, limits.binc),
+        };
+        let time = match time {
+            Some(time) => time.saturating_sub(overhead),
+            None => return TimeManager { start, soft: None, hard: None },
+        };
+        let increment = increment.unwrap_or_default();
+        let moves_to_go = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, DEFAULT_MOVES_TO_GO);
+
+        let ceiling = time / MAX_TIME_FRACTION;
+        let soft = (time / moves_to_go + increment * 3 / 4).min(ceiling);
+        let hard = (soft * HARD_LIMIT_FACTOR).min(ceiling.max(soft));
+        TimeManager { start, soft: Some(soft), hard: Some(hard) }
+    }
+
+    ///The same budget counted from `start`, for a ponder search whose move
+    ///was played.
+    pub fn restarted(&self, start: Instant) -> TimeManager {
+        TimeManager { start, ..self.clone() }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        self.hard.is_some_and(|hard| self.elapsed() >= hard)
+    }
+
+    ///Whether there's time for another iteration. `stability` is how many
+    ///iterations in a row returned the same best move: an unsettled search
+    ///gets more than the soft limit, a settled one stops early.
+    pub fn start_iteration(&self, stability: u32) -> bool {
+        const SCALE_PERCENT: [u32; 5] = [200, 130, 100, 80, 60];
+        match self.soft {
+            None => true,
+            Some(soft) => {
+                let scale = SCALE_PERCENT[(stability as usize).min(SCALE_PERCENT.len() - 1)];
+                self.elapsed() < soft * scale / 100 / 2
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_go() {
+        let limits = Limits::from_go(&["wtime", "60000", "btime", "30000", "winc", "1000", "movestogo", "20"]);
+        assert_eq!(limits.wtime, Some(Duration::from_secs(60)));
+        assert_eq!(limits.btime, Some(Duration::from_secs(30)));
+        assert_eq!(limits.winc, Some(Duration::from_secs(1)));
+        assert_eq!(limits.movestogo, Some(20));
+        assert!(!limits.infinite);
+        assert!(Limits::from_go(&["infinite"]).infinite);
+        assert!(Limits::from_go(&["ponder", "wtime", "1000"]).ponder);
+        assert_eq!(Limits::from_go(&["depth", "x", "movetime"]), Limits::default());
+    }
+
+    #[test]
+    fn budgets() {
+        let limits = Limits::from_go(&["wtime", "60000", "btime", "1000", "movestogo", "20"]);
+        let white = TimeManager::new(&limits, Color::White, Duration::ZERO);
+        assert_eq!(white.soft, Some(Duration::from_secs(3)));
+        assert_eq!(white.hard, Some(Duration::from_secs(12)));
+
+        // Low on time, never more than a third of what's left.
+        let limits = Limits::from_go(&["btime", "1000", "movestogo", "2"]);
+        let black = TimeManager::new(&limits, Color::Black, Duration::from_millis(100));
+        assert_eq!(black.hard, Some(Duration::from_millis(300)));
+
+        let movetime = TimeManager::new(&Limits::from_go(&["movetime", "500"]), Color::White, Duration::from_millis(50));
+        assert_eq!(movetime.hard, Some(Duration::from_millis(450)));
+        assert!(TimeManager::new(&Limits::depth(5), Color::White, Duration::ZERO).start_iteration(0));
+    }
+
+    #[test]
+    fn movetime_is_used_up() {
+        let limits = Limits::from_go(&["movetime", "1000"]);
+        let time = TimeManager::new(&limits, Color::White, Duration::ZERO)
+            .restarted(Instant::now() - Duration::from_millis(600));
+        assert!(time.start_iteration(10));
+        assert!(!time.hard_limit_reached());
+        let time = time.restarted(Instant::now() - Duration::from_millis(1000));
+        assert!(time.hard_limit_reached());
+    }
+}
@@ -0,0 +1,253 @@
+//!Lock-free transposition table shared by every search thread.
+//!
+//!Each slot holds the entry and the entry XORed with the position's key. A
+//!slot torn by two threads writing at once no longer XORs back to the key,
+//!so it reads as a miss instead of as the wrong position.
+use chess::repr::*;
+use crate::evaluation::{Score, MATE_BOUND};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 65536;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bound {
+    ///The score is exact.
+    Exact,
+    ///The search failed high, the score is at least this.
+    Lower,
+    ///The search failed low, the score is at most this.
+    Upper,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub depth: u8,
+    pub bound: Bound,
+    ///Stored relative to the entry's position, see `Entry::score`.
+    score: i16,
+    pub best_move: Option<Move>,
+    pub age: u8,
+}
+
+impl Entry {
+    ///Mates are stored as the distance from the entry's position, so they
+    ///have to be turned back into the distance from the root at `ply`.
+    pub fn score(&self, ply: u32) -> Score {
+        let score = self.score as Score;
+        if score >= MATE_BOUND {
+            score - ply as Score
+        } else if score <= -MATE_BOUND {
+            score + ply as Score
+        } else {
+            score
+        }
+    }
+
+    // Bits 0-19 move, 20-35 score, 36-43 depth, 44-45 bound, 46-51 age.
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        pack_move(self.best_move) |
+            (self.score as u16 as u64) << 20 |
+            (self.depth as u64) << 36 |
+            bound << 44 |
+            ((self.age & AGE_MASK) as u64) << 46
+    }
+
+    fn unpack(data: u64) -> Entry {
+        let bound = match (data >> 44) & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Entry {
+            depth: (data >> 36) as u8,
+            bound,
+            score: (data >> 20) as u16 as i16,
+            best_move: unpack_move(data),
+            age: (data >> 46) as u8 & AGE_MASK,
+        }
+    }
+}
+
+const AGE_MASK: u8 = 0x3f;
+const ENTRY_SIZE: usize = 16;
+
+// Bits 0-5 from, 6-11 to, 12-14 piece, 15-17 kind. Kind 0 is no move.
+fn pack_move(mv: Option<Move>) -> u64 {
+    let (from, to, piece, kind) = match mv {
+        None => return 0,
+        Some(Move::Normal(from, to, piece)) => (from, to, piece, 1),
+        Some(Move::EnPassant(from, to)) => (from, to, Piece::Pawn, 2),
+        Some(Move::Promotion(from, to, piece)) => (from, to, piece, 3),
+        Some(Move::KingsideCastle) => (Square::A1, Square::A1, Piece::King, 4),
+        Some(Move::QueensideCastle) => (Square::A1, Square::A1, Piece::King, 5),
+    };
+    from as u64 | (to as u64) << 6 | (piece as u64) << 12 | kind << 15
+}
+
+fn unpack_move(data: u64) -> Option<Move> {
+    let from = Square::from((data & 0x3f) as u8);
+    let to = Square::from((data >> 6 & 0x3f) as u8);
+    let piece = Piece::ALL[(data >> 12 & 7) as usize % Piece::ALL.len()];
+    match data >> 15 & 7 {
+        1 => Some(Move::Normal(from, to, piece)),
+        2 => Some(Move::EnPassant(from, to)),
+        3 => Some(Move::Promotion(from, to, piece)),
+        4 => Some(Move::KingsideCastle),
+        5 => Some(Move::QueensideCastle),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    ///A table taking about `megabytes` of memory, at least one entry.
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let len = (megabytes.min(MAX_HASH_MB) * 1024 * 1024 / ENTRY_SIZE).max(1);
+        TranspositionTable {
+            slots: (0..len).map(|_| Slot::default()).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    ///Marks the entries written so far as older than the ones to come, so
+    ///they get replaced first.
+    pub fn new_search(&self) {
+        let age = self.age.load(Ordering::Relaxed);
+        self.age.store(age.wrapping_add(1) & AGE_MASK, Ordering::Relaxed);
+    }
+
+    fn slot(&self, hash: u64) -> &Slot {
+        let index = (hash as u128 * self.slots.len() as u128) >> 64;
+        &self.slots[index as usize]
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let slot = self.slot(hash);
+        let data = slot.data.load(Ordering::Relaxed);
+        let key = slot.key.load(Ordering::Relaxed);
+        match data != 0 && key ^ data == hash {
+            true => Some(Entry::unpack(data)),
+            false => None,
+        }
+    }
+
+    ///`score` is from the root's point of view at `ply`. An entry for
+    ///another position is replaced only if it is from an older search or
+    ///the new one is at least as deep, one for the same position unless it
+    ///is much deeper and the new one isn't exact.
+    pub fn store(&self, hash: u64, depth: u32, ply: u32, bound: Bound, score: Score, best_move: Option<Move>) {
+        let age = self.age.load(Ordering::Relaxed);
+        let slot = self.slot(hash);
+        let depth = depth.min(u8::MAX as u32) as u8;
+
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let same_position = slot.key.load(Ordering::Relaxed) ^ old_data == hash;
+        let mut best_move = best_move;
+        if old_data != 0 {
+            let old = Entry::unpack(old_data);
+            let replace = if same_position {
+                bound == Bound::Exact || depth + 3 > old.depth
+            } else {
+                old.age != age || depth >= old.depth
+            };
+            if !replace {
+                return;
+            }
+            // A fail low has no best move of its own, keep the old one.
+            if same_position && best_move.is_none() {
+                best_move = old.best_move;
+            }
+        }
+
+        let score = if score >= MATE_BOUND {
+            score + ply as Score
+        } else if score <= -MATE_BOUND {
+            score - ply as Score
+        } else {
+            score
+        };
+        let data = Entry { depth, bound, score: score as i16, best_move, age }.pack();
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    ///Permille of the table used by the current search, sampled from the
+    ///first thousand entries.
+    pub fn hashfull(&self) -> u32 {
+        let age = self.age.load(Ordering::Relaxed);
+        let sample = &self.slots[..self.slots.len().min(1000)];
+        let used = sample.iter().filter(|slot| {
+            let data = slot.data.load(Ordering::Relaxed);
+            data != 0 && Entry::unpack(data).age == age
+        }).count();
+        (used * 1000 / sample.len()) as u32
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::MATE;
+
+    // Far enough apart to land in different slots.
+    const FIRST: u64 = 0x1234_5678_9abc_def0;
+    const SECOND: u64 = 0xfedc_ba98_7654_3210;
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let mv = Move::Promotion(Square::B7, Square::A8, Piece::Knight);
+        tt.store(FIRST, 5, 0, Bound::Lower, -120, Some(mv));
+        let entry = tt.probe(FIRST).unwrap();
+        assert_eq!((entry.depth, entry.bound, entry.score(0), entry.best_move), (5, Bound::Lower, -120, Some(mv)));
+        assert_eq!(tt.probe(FIRST + 1), None);
+
+        // A mate found 3 plies below the root at ply 2 is 1 ply from there.
+        tt.store(SECOND, 1, 2, Bound::Exact, MATE - 3, Some(Move::QueensideCastle));
+        assert_eq!(tt.probe(SECOND).unwrap().score(0), MATE - 1);
+        assert_eq!(tt.probe(SECOND).unwrap().score(4), MATE - 5);
+
+        tt.clear();
+        assert_eq!(tt.probe(FIRST), None);
+    }
+
+    #[test]
+    fn replacement() {
+        let tt = TranspositionTable::new(0);
+        tt.store(1, 10, 0, Bound::Exact, 0, Some(Move::KingsideCastle));
+        tt.store(2, 4, 0, Bound::Exact, 0, None);
+        assert!(tt.probe(2).is_none());
+
+        tt.store(1, 8, 0, Bound::Upper, 30, None);
+        assert_eq!(tt.probe(1).unwrap().best_move, Some(Move::KingsideCastle));
+
+        tt.new_search();
+        tt.store(2, 4, 0, Bound::Exact, 0, None);
+        assert!(tt.probe(2).is_some());
+    }
+}
@@ -1,64 +1,338 @@
-use std::io;
+use std::io::{self, BufRead};
 use chess::*;
+use chess::notation::{MoveFormatter, Notation};
 use crate::*;
-use std::collections::HashMap;
+use crate::evaluation::{Score, MATE, MATE_BOUND};
+use crate::options::{OptionKind, Options};
+use crate::time::Limits;
+use crate::tt::{Bound, TranspositionTable, DEFAULT_HASH_MB, MAX_HASH_MB};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+///Depth searched by a bare `go`.
+const DEFAULT_DEPTH: u32 = 6;
+const MAX_MOVE_OVERHEAD: i64 = 5000;
+const MAX_THREADS: i64 = 256;
+const MAX_MULTIPV: i64 = 256;
 
 // fn make_moves(state: &Chessboard, moves: ) -> Chessboard {
 
 
 // }
 
-fn go_settings<'a>(input: &[&'a str]) -> HashMap<&'a str, i32> {
-    let mut map = HashMap::new();
+///What `setoption` changes.
+struct Settings {
+    search: SearchOptions,
+    tt: Arc<TranspositionTable>,
+    formatter: MoveFormatter,
+}
+
+///Every option the engine has. Search and evaluation parameters are
+///registered as spin options at the end, so they can be tuned from a GUI
+///or a tuner.
+fn engine_options() -> Options<Settings> {
+    let defaults = SearchOptions::default();
+    let mut options: Options<Settings> = Options::default();
+    options.register("Hash", OptionKind::Spin {
+        default: DEFAULT_HASH_MB as i64,
+        min: 1,
+        max: MAX_HASH_MB as i64,
+        set: |settings, megabytes| settings.tt = Arc::new(TranspositionTable::new(megabytes as usize)),
+    });
+    options.register("Clear Hash", OptionKind::Button { press: |settings| settings.tt.clear() });
+    options.register("Threads", OptionKind::Spin {
+        default: defaults.threads as i64,
+        min: 1,
+        max: MAX_THREADS,
+        set: |settings, threads| settings.search.threads = threads as usize,
+    });
+    options.register("MultiPV", OptionKind::Spin {
+        default: defaults.multipv as i64,
+        min: 1,
+        max: MAX_MULTIPV,
+        set: |settings, lines| settings.search.multipv = lines as usize,
+    });
+    // The GUI decides when to ponder, the engine only has to know `go ponder`.
+    options.register("Ponder", OptionKind::Check { default: false, set: |_, _| {} });
+    // There is no opening book yet, the engine always searches.
+    options.register("OwnBook", OptionKind::Check { default: false, set: |_, _| {} });
+    options.register("Move Overhead", OptionKind::Spin {
+        default: defaults.move_overhead.as_millis() as i64,
+        min: 0,
+        max: MAX_MOVE_OVERHEAD,
+        set: |settings, millis| settings.search.move_overhead = Duration::from_millis(millis as u64),
+    });
+    options.register("Skill Level", OptionKind::Spin {
+        default: defaults.skill_level as i64,
+        min: 0,
+        max: MAX_SKILL_LEVEL as i64,
+        set: |settings, level| settings.search.skill_level = level as u32,
+    });
+    options.register("UCI_Chess960", OptionKind::Check {
+        default: false,
+        set: |settings, chess960| settings.formatter.chess960 = chess960,
+    });
+
+    // The selective search parameters all turn their technique off at 0.
+    options.register("QSearch SEE", OptionKind::Check {
+        default: defaults.qsearch_see,
+        set: |settings, value| settings.search.qsearch_see = value,
+    });
+    options.register("Null Move Reduction", OptionKind::Spin {
+        default: defaults.null_move_reduction as i64,
+        min: 0,
+        max: 6,
+        set: |settings, value| settings.search.null_move_reduction = value as u32,
+    });
+    options.register("LMR Divisor", OptionKind::Spin {
+        default: defaults.lmr_divisor as i64,
+        min: 0,
+        max: 1000,
+        set: |settings, value| settings.search.lmr_divisor = value as u32,
+    });
+    options.register("Reverse Futility Margin", OptionKind::Spin {
+        default: defaults.reverse_futility_margin as i64,
+        min: 0,
+        max: 1000,
+        set: |settings, value| settings.search.reverse_futility_margin = value as Score,
+    });
+    options.register("Futility Margin", OptionKind::Spin {
+        default: defaults.futility_margin as i64,
+        min: 0,
+        max: 1000,
+        set: |settings, value| settings.search.futility_margin = value as Score,
+    });
+    options.register("Razor Margin", OptionKind::Spin {
+        default: defaults.razor_margin as i64,
+        min: 0,
+        max: 1000,
+        set: |settings, value| settings.search.razor_margin = value as Score,
+    });
+    options.register("LMP Base", OptionKind::Spin {
+        default: defaults.late_move_pruning as i64,
+        min: 0,
+        max: 20,
+        set: |settings, value| settings.search.late_move_pruning = value as u32,
+    });
+
+    let eval = defaults.eval;
+    let weight = |default: Score, max: i64, set: fn(&mut Settings, i64)| OptionKind::Spin { default: default as i64, min: 0, max, set };
+    options.register("Pawn Value", weight(eval.pawn, 1000, |settings, value| settings.search.eval.pawn = value as Score));
+    options.register("Knight Value", weight(eval.knight, 2000, |settings, value| settings.search.eval.knight = value as Score));
+    options.register("Bishop Value", weight(eval.bishop, 2000, |settings, value| settings.search.eval.bishop = value as Score));
+    options.register("Rook Value", weight(eval.rook, 3000, |settings, value| settings.search.eval.rook = value as Score));
+    options.register("Queen Value", weight(eval.queen, 5000, |settings, value| settings.search.eval.queen = value as Score));
+    options.register("Piece Square Percent", weight(eval.piece_square_percent, 400, |settings, value| {
+        settings.search.eval.piece_square_percent = value as Score
+    }));
+    options.register("Mobility", weight(eval.mobility, 50, |settings, value| settings.search.eval.mobility = value as Score));
+    options
+}
+
+///Splits the arguments of `setoption name <name> [value <value>]`.
+fn parse_setoption(input: &[&str]) -> (String, Option<String>) {
+    let value = input.iter().position(|&token| token == "value");
+    let name = input[..value.unwrap_or(input.len())].iter()
+        .skip_while(|&&token| token == "name")
+        .copied().collect::<Vec<_>>().join(" ");
+    let value = value.map(|index| input[index + 1..].join(" "));
+    (name, value)
+}
+
+///Reads the arguments of `position [startpos | fen <fen>] [moves <moves>]`.
+fn parse_position(args: &[&str], formatter: &MoveFormatter) -> Result<Chessboard, ChessError> {
+    let moves = args.iter().position(|&token| token == "moves").unwrap_or(args.len());
+    let mut chessboard = match args.first() {
+        Some(&"startpos") => Chessboard::starting(),
+        Some(&"fen") => Chessboard::from_fen(&args[1..moves].join(" "))?,
+        _ => return Err(ChessError::InvalidFEN("Missing position".to_owned())),
+    };
+    for mv in args.iter().skip(moves + 1) {
+        let mv = formatter.parse(&chessboard.position, mv)?;
+        chessboard = chessboard.make_move(mv);
+    }
+    Ok(chessboard)
+}
 
-    for (subcmd, val) in input.iter().step_by(2).zip(input.iter().skip(1).step_by(2)) { 
-        map.insert(*subcmd, val.parse().unwrap());
-    } 
-    map
+///`cp` in centipawns, or `mate` in moves, negative if the engine is the
+///one getting mated.
+fn format_score(score: Score) -> String {
+    if score >= MATE_BOUND {
+        format!("mate {}", (MATE - score + 1) / 2)
+    } else if score <= -MATE_BOUND {
+        format!("mate {}", -(MATE + score) / 2)
+    } else {
+        format!("cp {}", score)
+    }
 }
 
+///The `info` line for `info`, with moves played from `chessboard`.
+fn format_info(info: &Info, chessboard: &Chessboard, formatter: &MoveFormatter) -> String {
+    match *info {
+        Info::Iteration { multipv, score, bound, pv, stats, hashfull } => {
+            let bound = match bound {
+                Bound::Exact => "",
+                Bound::Lower => " lowerbound",
+                Bound::Upper => " upperbound",
+            };
+            let millis = stats.elapsed.as_millis() as u64;
+            let nps = stats.nodes * 1000 / millis.max(1);
+            let mut position = chessboard.clone();
+            let mut line = Vec::new();
+            for &mv in pv {
+                line.push(formatter.format(&position.position, mv));
+                position = position.make_move(mv);
+            }
+            format!("info depth {} seldepth {} multipv {} score {}{} nodes {} nps {} time {} hashfull {} pv {}",
+                    stats.depth, stats.seldepth, multipv, format_score(score), bound, stats.nodes, nps, millis,
+                    hashfull, line.join(" "))
+        }
+        Info::CurrentMove { depth, mv, number } => {
+            format!("info depth {} currmove {} currmovenumber {}", depth, formatter.format(&chessboard.position, mv), number)
+        }
+    }
+}
+
+///`bestmove`, with the move to ponder on if there is one.
+fn format_bestmove(result: &SearchResult, chessboard: &Chessboard, formatter: &MoveFormatter) -> String {
+    match (result.best, result.ponder) {
+        (Some(best), Some(ponder)) => format!("bestmove {} ponder {}",
+            formatter.format(&chessboard.position, best),
+            formatter.format(&chessboard.make_move(best).position, ponder)),
+        (Some(best), None) => format!("bestmove {}", formatter.format(&chessboard.position, best)),
+        (None, _) => "bestmove 0000".to_string(),
+    }
+}
+
+///Reads stdin on a thread of its own, so commands keep coming in while the
+///engine searches. The channel closes when the input ends.
+fn read_commands() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+///A search running on a thread of its own.
+struct Searching {
+    control: Arc<SearchControl>,
+    infinite: bool,
+    thread: JoinHandle<()>,
+}
+
+impl Searching {
+    ///Searches until `limits` run out or `control` stops it, then prints
+    ///`bestmove`. A search for `go infinite` or `go ponder` doesn't answer
+    ///before the GUI sends `stop` or `ponderhit`, even if it is done.
+    fn start(chessboard: Chessboard, limits: Limits, tt: Arc<TranspositionTable>, options: SearchOptions,
+             formatter: MoveFormatter) -> Searching {
+        let control = Arc::new(SearchControl::new(limits.ponder));
+        let infinite = limits.infinite;
+        let thread = {
+            let control = Arc::clone(&control);
+            thread::spawn(move || {
+                let report = |info: Info| println!("{}", format_info(&info, &chessboard, &formatter));
+                let result = search(&chessboard, &limits, &tt, &options, &control, &report);
+                while (limits.infinite || control.is_pondering()) && !control.is_stopped() {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                println!("{}", format_bestmove(&result, &chessboard, &formatter));
+            })
+        };
+        Searching { control, infinite, thread }
+    }
+
+    fn wait(self) {
+        self.thread.join().expect("the search thread panicked");
+    }
+
+    ///Lets a search with limits finish, but stops one that would only end
+    ///on `stop` or `ponderhit`, since those can't be read while waiting.
+    fn finish(self) {
+        if self.infinite || self.control.is_pondering() {
+            self.control.stop();
+        }
+        self.wait();
+    }
+}
 
 pub fn run() {
+    run_commands(read_commands())
+}
 
+///Answers `commands` until `quit` or the end of them.
+fn run_commands(commands: impl IntoIterator<Item = String>) {
     let mut chessboard = Chessboard::starting();
-    loop {
+    let options = engine_options();
+    let mut settings = Settings {
+        search: SearchOptions::default(),
+        tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+        formatter: MoveFormatter::new(Notation::Uci),
+    };
+    let mut searching: Option<Searching> = None;
+    for input in commands {
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
         let input: Vec<&str> = input.split_whitespace().collect();
-        
-        match input[0] {
+        let command = match input.first() {
+            Some(&command) => command,
+            None => continue,
+        };
+        // The GUI shouldn't send anything else during a search, but if it
+        // does the search gets to finish first.
+        if !matches!(command, "stop" | "ponderhit" | "isready" | "quit") {
+            if let Some(searching) = searching.take() {
+                searching.finish();
+            }
+        }
+
+        match command {
             "uci" => {
                 println!("id name Sah-O-Matic-2000");
                 println!("id author Florin-Vlad Sabau");
+                for declaration in options.declarations() {
+                    println!("{}", declaration);
+                }
                 println!("uciok");
             },
             "position" => {
-                let mut moves = Vec::new();
-                if input[1] == "startpos" {
-                    chessboard = Chessboard::starting(); 
-                    if input.len() > 3 {
-                        moves = input[3..].iter().collect();
-                    }
-                } else {
-                    chessboard = Chessboard::from_fen(&input[2..8].join(" ")).unwrap();
-                    if input.len() > 9 {
-                        moves = input[9..].iter().collect();
-                    }
-                }
-
-                for mv in moves.iter() {
-                    let mv = chessboard.parse_move(mv).unwrap();
-                    chessboard = chessboard.make_move(mv);
+                match parse_position(&input[1..], &settings.formatter) {
+                    Ok(position) => chessboard = position,
+                    Err(error) => println!("info string {}", error),
                 }
-                
             },
             "go" => {
-                let settings = go_settings(&input[1..]);
-                let depth = *settings.get("depth").unwrap_or(&6) as u32;
-                let mov = best_move(&chessboard, depth);
-                println!("bestmove {}",mov);
+                let mut limits = Limits::from_go(&input[1..]);
+                if limits == Limits::default() {
+                    limits.depth = Some(DEFAULT_DEPTH);
+                }
+                searching = Some(Searching::start(chessboard.clone(), limits, Arc::clone(&settings.tt),
+                                                  settings.search.clone(), settings.formatter));
+            },
+            "stop" => {
+                if let Some(searching) = &searching {
+                    searching.control.stop();
+                }
+            },
+            "ponderhit" => {
+                if let Some(searching) = &searching {
+                    searching.control.ponderhit();
+                }
+            },
+            "quit" => break,
+            "setoption" => {
+                let (name, value) = parse_setoption(&input[1..]);
+                if let Err(error) = options.set(&mut settings, &name, value.as_deref()) {
+                    println!("info string {}", error);
+                }
             },
+            "ucinewgame" => settings.tt.clear(),
             "isready" => {
                 println!("readyok");   
             },
@@ -66,4 +340,82 @@ pub fn run() {
         }
     }
 
+    if let Some(searching) = searching {
+        searching.control.stop();
+        searching.wait();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_lines() {
+        assert_eq!(format_score(-35), "cp -35");
+        assert_eq!(format_score(MATE - 1), "mate 1");
+        assert_eq!(format_score(MATE - 3), "mate 2");
+        assert_eq!(format_score(2 - MATE), "mate -1");
+
+        let chessboard = Chessboard::from_fen("7k/8/5K2/8/8/8/8/R7 w - - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let lines = std::sync::Mutex::new(Vec::new());
+        let report = |info: Info| lines.lock().unwrap().push(format_info(&info, &chessboard, &MoveFormatter::new(Notation::Uci)));
+        search(&chessboard, &Limits::depth(4), &tt, &SearchOptions::default(), &SearchControl::default(), &report);
+        let lines = lines.into_inner().unwrap();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("info depth 1 seldepth "));
+        assert!(lines[3].contains(" multipv 1 score mate 2 nodes "));
+        assert_eq!(lines[3].split(" pv ").nth(1).unwrap().split(' ').count(), 3);
+    }
+
+    #[test]
+    fn positions() {
+        let formatter = MoveFormatter::new(Notation::Uci);
+        let chessboard = parse_position(&["startpos", "moves", "e2e4", "e7e5"], &formatter).unwrap();
+        assert_eq!(chessboard.position.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        let fen = "7k/8/5K2/8/8/8/8/R7 w - - 0 1".split(' ').collect::<Vec<_>>();
+        let chessboard = parse_position(&[&["fen"], &fen[..], &["moves", "a1a7"]].concat(), &formatter).unwrap();
+        assert_eq!(chessboard.position.to_fen(), "7k/R7/5K2/8/8/8/8/8 b - - 1 1");
+
+        assert!(parse_position(&["startpos", "moves", "e2e5"], &formatter).is_err());
+        assert!(parse_position(&[], &formatter).is_err());
+        let crowded = "QQQQQQQk/Q6Q/Q6Q/Q3Q2Q/Q6Q/Q6Q/Q6Q/KQQQQQQQ w - - 0 1".split(' ').collect::<Vec<_>>();
+        assert!(parse_position(&[&["fen"], &crowded[..]].concat(), &formatter).is_err());
+    }
+
+    #[test]
+    fn commands_during_an_endless_search() {
+        let commands = ["position startpos", "go infinite", "position startpos moves e2e4", "go ponder movetime 50",
+                        "ucinewgame", "go depth 1", "quit"];
+        let (done, finished) = mpsc::channel();
+        thread::spawn(move || {
+            run_commands(commands.iter().map(|command| command.to_string()));
+            done.send(()).unwrap();
+        });
+        assert!(finished.recv_timeout(Duration::from_secs(10)).is_ok(), "the engine hangs");
+    }
+
+    #[test]
+    fn engine_options_apply() {
+        let options = engine_options();
+        for name in ["Hash", "Threads", "MultiPV", "Ponder", "OwnBook", "Move Overhead", "Clear Hash", "Skill Level",
+                     "UCI_Chess960", "LMR Divisor"].iter() {
+            assert!(options.get(name).is_some(), "{} is missing", name);
+        }
+
+        let mut settings = Settings {
+            search: SearchOptions::default(),
+            tt: Arc::new(TranspositionTable::new(1)),
+            formatter: MoveFormatter::new(Notation::Uci),
+        };
+        options.set(&mut settings, "threads", Some("4")).unwrap();
+        options.set(&mut settings, "MultiPV", Some("3")).unwrap();
+        options.set(&mut settings, "UCI_Chess960", Some("true")).unwrap();
+        assert_eq!((settings.search.threads, settings.search.multipv), (4, 3));
+        assert!(settings.formatter.chess960);
+        assert!(options.set(&mut settings, "Skill Level", Some("21")).is_err());
+        assert_eq!(settings.search.skill_level, MAX_SKILL_LEVEL);
+    }
 }
@@ -1,5 +1,6 @@
 use pyo3::prelude::*;
 use chess::{Chessboard, ChessError};
+use chess::game::Game;
 use chess::repr::*;
 
 struct PyChessError(pub ChessError);
@@ -22,6 +23,7 @@ impl std::convert::From<PyChessError> for PyErr {
 #[pyclass(name="Chessboard")]
 struct PyChessboard {
     board: Chessboard,
+    game: Game,
 }
 
 #[pymethods]
@@ -29,7 +31,8 @@ impl PyChessboard {
     #[new]
     fn new() -> Self {
         PyChessboard {
-            board: Chessboard::starting()
+            board: Chessboard::starting(),
+            game: Game::default(),
         }
     }
     
@@ -73,13 +76,20 @@ impl PyChessboard {
     }
 
     fn undo(&mut self) -> Result<(), PyChessError> {
-        self.board = self.board.previous()?;
-        self.board = self.board.previous()?;
-        Ok(())
+        // The moves stay in the game tree, playing something else afterwards
+        // starts a variation instead of throwing them away.
+        let undone = self.game.back() && self.game.back();
+        self.board = self.game.chessboard();
+        match undone {
+            true => Ok(()),
+            false => Err(ChessError::NoPreviousPos.into()),
+        }
     }
 
     fn make_move(&mut self, mov: &str) -> Result<(), PyChessError> {
-        self.board = self.board.make_str_move(mov)?;
+        let mov = self.board.parse_move(mov)?;
+        self.game.play(mov)?;
+        self.board = self.board.make_move(mov);
         Ok(())
     }
 }